@@ -1,53 +1,136 @@
 //! State machine for Math Drill.
 //!
 //! States:
-//!   Menu        — choose operation, difficulty, start quiz
-//!   Playing     — answering problems, timer running
-//!   Feedback    — brief correct/wrong display
-//!   Results     — session summary with stats
+//!   ResumePrompt — at launch, offer to continue a session saved on quit
+//!   ConfirmDiscard — Menu pressed on the resume prompt; confirm before
+//!                 deleting the saved session (logged as incomplete)
+//!   Menu        — choose operation, difficulty, mode, start quiz
+//!   Playing     — answering problems, timer running (a Sprint also ends here
+//!                 when its countdown expires; with a per-problem time limit,
+//!                 or in Survival, a problem can time out into Feedback)
+//!   Feedback    — brief correct/wrong display (a miss in Zen is retried)
+//!   Paused      — session frozen with the problem hidden (Menu key, or the
+//!                 app losing focus); every timer resumes where it stopped
+//!   ConfirmAbandon — Menu pressed while paused; confirm before throwing the
+//!                 session away (it is logged to history as incomplete)
+//!   Results     — session summary with stats; can start a retry round of
+//!                 the missed problems, which returns here when cleared
 //!   BestScores  — all-time bests per difficulty
+//!   Heatmap     — per-fact accuracy and speed for one operation's table
 
 extern crate alloc;
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
-use alloc::format;
 
+use serde::{Deserialize, Serialize};
+
+use crate::facts::{FactTable, GRID_MAX};
+use crate::fraction;
+use crate::history::{unix_time_secs, Attempt, SessionRecord};
 use crate::problems::*;
-use crate::rng::Rng;
-use crate::storage::{BestStats, Storage};
+use crate::rng::{shuffle, RandomSource, SeededRng};
+use crate::storage::{BestStats, MemoryStore, Settings, Storage};
 
-const KEY_UP: char = '\u{F700}';
-const KEY_DOWN: char = '\u{F701}';
-const KEY_LEFT: char = '\u{F702}';
-const KEY_RIGHT: char = '\u{F703}';
-const KEY_ENTER: char = '\u{000D}';
-const KEY_BACKSPACE: char = '\u{0008}';
-const KEY_MENU: char = '\u{2234}';
+pub(crate) const KEY_UP: char = '\u{F700}';
+pub(crate) const KEY_DOWN: char = '\u{F701}';
+pub(crate) const KEY_LEFT: char = '\u{F702}';
+pub(crate) const KEY_RIGHT: char = '\u{F703}';
+pub(crate) const KEY_ENTER: char = '\u{000D}';
+pub(crate) const KEY_BACKSPACE: char = '\u{0008}';
+pub(crate) const KEY_MENU: char = '\u{2234}';
 
-const PROBLEMS_PER_SESSION: usize = 10;
+/// Operation modes in the order the menu cycles through them.
+pub const OP_MODES: [OpMode; 15] = [
+    OpMode::Single(Operation::Add),
+    OpMode::Single(Operation::Subtract),
+    OpMode::Single(Operation::Multiply),
+    OpMode::Single(Operation::Divide),
+    OpMode::Single(Operation::Percent),
+    OpMode::Single(Operation::Square),
+    OpMode::Single(Operation::Cube),
+    OpMode::Single(Operation::Power),
+    OpMode::Single(Operation::SquareRoot),
+    OpMode::Single(Operation::CubeRoot),
+    OpMode::Mixed,
+    OpMode::Expressions,
+    OpMode::Fractions,
+    OpMode::Decimals,
+    OpMode::Practice,
+];
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Session lengths offered on the menu.
+pub const SESSION_LENGTHS: [usize; 5] = [5, 10, 20, 50, 100];
+const DEFAULT_SESSION_LENGTH: usize = 10;
+
+/// Sprint durations offered on the menu, in seconds.
+pub const SPRINT_SECS: [u32; 3] = [30, 60, 120];
+const DEFAULT_SPRINT_SECS: u32 = 60;
+
+/// Session modes in menu order; a Sprint starts at the default duration.
+const SESSION_KINDS: [SessionKind; 4] = [
+    SessionKind::Standard,
+    SessionKind::Sprint(DEFAULT_SPRINT_SECS),
+    SessionKind::Survival,
+    SessionKind::Zen,
+];
+
+/// Per-problem time limits offered on the menu, in seconds; 0 is off.
+pub const PROBLEM_LIMIT_SECS: [u32; 5] = [0, 5, 10, 15, 30];
+/// How often the time-limit bar is redrawn as it shrinks.
+const LIMIT_BAR_STEP_MS: u64 = 500;
+
+/// Lives at the start of a Survival run.
+pub const SURVIVAL_LIVES: u32 = 3;
+/// Time allowed per Survival problem when no time limit is set.
+pub const SURVIVAL_PROBLEM_SECS: u32 = 10;
+/// Survival operand ranges grow after this many problems.
+const SURVIVAL_LEVEL_EVERY: usize = 5;
+
+/// How long the correct/wrong screen stays up before auto-advancing.
+const FEEDBACK_MS: u64 = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum AppState {
+    ResumePrompt,
+    ConfirmDiscard,
     Menu,
     Playing,
     Feedback,
+    Paused,
+    ConfirmAbandon,
     Results,
     BestScores,
+    Heatmap,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MenuField {
     Operation,
     Difficulty,
+    Find,
+    Negatives,
+    Mode,
+    Length,
+    TimeLimit,
     Start,
+    Heatmap,
     BestScores,
 }
 
 /// Which operation mode the quiz uses.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum OpMode {
     Single(Operation),
     Mixed,
+    /// Three to five operands with mixed operators, e.g. `3 + 4 x 5 - 2`.
+    Expressions,
+    /// Proper and mixed fractions with all four operations.
+    Fractions,
+    /// Fixed-point decimals with all four operations.
+    Decimals,
+    /// Spaced repetition over the user's weak and due facts.
+    Practice,
 }
 
 impl OpMode {
@@ -55,10 +138,103 @@ impl OpMode {
         match self {
             OpMode::Single(op) => op.label(),
             OpMode::Mixed => "Mixed",
+            OpMode::Expressions => "Expressions",
+            OpMode::Fractions => "Fractions",
+            OpMode::Decimals => "Decimals",
+            OpMode::Practice => "Practice",
+        }
+    }
+
+    /// Whether the Find setting applies: only to modes that ask plain
+    /// two-operand facts of the four operations.
+    pub fn allows_blank(&self) -> bool {
+        match self {
+            OpMode::Single(op) => op.is_arithmetic(),
+            OpMode::Mixed | OpMode::Practice => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the Negatives setting applies: only to the four
+    /// operations, alone or mixed.
+    pub fn allows_negatives(&self) -> bool {
+        match self {
+            OpMode::Single(op) => op.is_arithmetic(),
+            OpMode::Mixed => true,
+            _ => false,
+        }
+    }
+}
+
+/// How a session is bounded.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SessionKind {
+    /// A fixed number of problems (`session_length`).
+    #[default]
+    Standard,
+    /// As many problems as possible in the given number of seconds.
+    Sprint(u32),
+    /// Endless, with growing operands, until `SURVIVAL_LIVES` are lost.
+    Survival,
+    /// Untimed and unscored until the user leaves; misses are retried.
+    Zen,
+}
+
+impl SessionKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionKind::Standard => "Standard",
+            SessionKind::Sprint(_) => "Sprint",
+            SessionKind::Survival => "Survival",
+            SessionKind::Zen => "Zen",
         }
     }
 }
 
+/// An in-flight session as saved on quit or loss of focus. Times are kept
+/// relative to the pause, since the ticktimer restarts from 0 on reboot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub op_mode: OpMode,
+    pub difficulty: Difficulty,
+    pub session_length: usize,
+    pub session_kind: SessionKind,
+    pub problem_limit_secs: [u32; 3],
+    #[serde(default)]
+    pub find_operand: bool,
+    #[serde(default)]
+    pub negatives: bool,
+    pub seed: u64,
+    pub rng_state: u64,
+    /// Playing or Feedback: where to pick up.
+    pub state: AppState,
+    pub current_problem: Option<Problem>,
+    pub answer_buffer: String,
+    pub problem_num: usize,
+    pub correct_count: u32,
+    pub streak: u32,
+    pub best_streak: u32,
+    pub lives: u32,
+    pub total_time_ms: u64,
+    /// Time already spent on the current problem.
+    pub problem_elapsed_ms: u64,
+    /// Time to the first keystroke, if there was one.
+    pub first_key_elapsed_ms: Option<u64>,
+    pub backspaces: u32,
+    pub sprint_left_ms: u64,
+    pub feedback_correct: bool,
+    pub feedback_problem: Option<Problem>,
+    #[serde(default)]
+    pub feedback_user_input: String,
+    #[serde(default)]
+    pub feedback_timed_out: bool,
+    pub feedback_elapsed_ms: u64,
+    pub session_problems: Vec<Attempt>,
+    pub retry_round: u32,
+    pub retry_queue: Vec<Problem>,
+    pub retry_missed: Vec<Problem>,
+}
+
 pub struct MathDrillApp {
     pub state: AppState,
     pub needs_redraw: bool,
@@ -67,6 +243,14 @@ pub struct MathDrillApp {
     pub menu_field: MenuField,
     pub op_mode: OpMode,
     pub difficulty: Difficulty,
+    pub session_length: usize,
+    pub session_kind: SessionKind,
+    /// Per-problem time limit in seconds for each difficulty (0 = none).
+    pub problem_limit_secs: [u32; 3],
+    /// Ask for a missing operand (`7 x ? = 56`) instead of the result.
+    pub find_operand: bool,
+    /// Give the four operations operands of either sign (`7 - (-3)`).
+    pub negatives: bool,
 
     // Playing
     pub current_problem: Option<Problem>,
@@ -77,18 +261,75 @@ pub struct MathDrillApp {
     pub best_streak: u32,
     pub problem_start_ms: u64,
     pub total_time_ms: u64,
+    /// When the first key was typed for the current problem.
+    pub first_key_at: Option<u64>,
+    pub backspaces: u32,
+    /// Problems are drawn from a generator seeded once per session,
+    /// so any session can be replayed from `session_rng.seed()`.
+    pub session_rng: SeededRng,
+    /// When a Sprint's countdown runs out.
+    pub sprint_end_ms: u64,
+    /// Lives left in a Survival run.
+    pub lives: u32,
+    /// Latest time seen by `handle_key` or `tick`, for drawing countdowns.
+    pub now_ms: u64,
 
     // Feedback
     pub feedback_correct: bool,
     pub feedback_problem: Option<Problem>,
-    pub feedback_user_answer: i32,
+    /// What was typed, as shown back on a wrong answer.
+    pub feedback_user_input: String,
+    /// Whether the problem ran out of time rather than being answered.
+    pub feedback_timed_out: bool,
     pub feedback_timer: u64,
 
+    // Paused
+    /// State to return to on resume.
+    pub paused_from: AppState,
+    pub paused_at: u64,
+
+    // Resume prompt
+    /// Session found in storage at launch, until resumed or discarded.
+    pub saved_session: Option<SavedSession>,
+    /// Whether storage holds a saved session that may now be stale.
+    session_stored: bool,
+
     // Results
-    pub session_problems: Vec<(Problem, i32, bool)>, // (problem, user_answer, correct)
+    pub session_problems: Vec<Attempt>,
+
+    // Retry-missed round (see `start_retry`), scored apart from the session
+    /// Current retry pass, or 0 outside a retry round.
+    pub retry_round: u32,
+    /// Problems left in this pass.
+    pub retry_queue: Vec<Problem>,
+    /// Problems missed in this pass, asked again in the next.
+    pub retry_missed: Vec<Problem>,
+    /// Passes it took to answer every missed problem correctly.
+    pub retry_cleared: Option<u32>,
+
+    // Best scores, cached per difficulty for the current length or Sprint (see `refresh_best`)
+    pub best_scores: [Option<BestStats>; 3],
+    pub sessions_logged: u32,
+
+    // Fact mastery
+    pub facts: FactTable,
+    pub heatmap_op: Operation,
+    /// Selected heatmap cell, 1..=GRID_MAX each way.
+    pub heatmap_row: i32,
+    pub heatmap_col: i32,
 
     // Storage
-    storage: Option<Storage>,
+    pub(crate) storage: Storage,
+    /// Set when any write since the session started failed; a later
+    /// successful write doesn't clear it.
+    pub save_failed: bool,
+    /// Whether `load_stored` has run with storage mounted. Until then
+    /// nothing stored can be replaced, and the load is retried.
+    stored_loaded: bool,
+    /// Menu settings changed before they could be saved.
+    settings_unsaved: bool,
+    /// Stored settings held back until the running session is over.
+    pending_settings: Option<Settings>,
 }
 
 impl MathDrillApp {
@@ -99,6 +340,11 @@ impl MathDrillApp {
             menu_field: MenuField::Operation,
             op_mode: OpMode::Single(Operation::Add),
             difficulty: Difficulty::Easy,
+            session_length: DEFAULT_SESSION_LENGTH,
+            session_kind: SessionKind::Standard,
+            find_operand: false,
+            negatives: false,
+            problem_limit_secs: [0; 3],
             current_problem: None,
             answer_buffer: String::new(),
             problem_num: 0,
@@ -107,50 +353,402 @@ impl MathDrillApp {
             best_streak: 0,
             problem_start_ms: 0,
             total_time_ms: 0,
+            first_key_at: None,
+            backspaces: 0,
+            session_rng: SeededRng::new(0),
+            sprint_end_ms: 0,
+            lives: SURVIVAL_LIVES,
+            now_ms: 0,
             feedback_correct: false,
             feedback_problem: None,
-            feedback_user_answer: 0,
+            feedback_user_input: String::new(),
+            feedback_timed_out: false,
             feedback_timer: 0,
+            paused_from: AppState::Playing,
+            paused_at: 0,
+            saved_session: None,
+            session_stored: false,
             session_problems: Vec::new(),
-            storage: None,
+            retry_round: 0,
+            retry_queue: Vec::new(),
+            retry_missed: Vec::new(),
+            retry_cleared: None,
+            best_scores: [None, None, None],
+            sessions_logged: 0,
+            facts: FactTable::new(),
+            heatmap_op: Operation::Multiply,
+            heatmap_row: 1,
+            heatmap_col: 1,
+            storage: Storage::new(Box::new(MemoryStore::new())),
+            save_failed: false,
+            stored_loaded: false,
+            settings_unsaved: false,
+            pending_settings: None,
         }
     }
 
+    /// Switch from the in-memory default to the PDDB.
+    #[cfg(target_os = "xous")]
     pub fn init_storage(&mut self) {
-        if let Ok(st) = Storage::new() {
-            self.storage = Some(st);
+        self.storage = Storage::new(Box::new(crate::storage::PddbStore::new()));
+        self.load_stored();
+    }
+
+    /// Load facts, settings and any session left over from the last run.
+    /// Run again once the PDDB mounts if it wasn't at launch (see
+    /// `load_if_mounted`): answers and settings from before then are kept.
+    pub fn load_stored(&mut self) {
+        self.stored_loaded = self.storage.is_mounted();
+        self.storage.load_facts(&mut self.facts);
+        let settings = self.storage.load_settings();
+        if self.settings_unsaved {
+            self.save_settings();
+        } else {
+            self.pending_settings = settings;
+        }
+        let saved = self.storage.load_session_in_progress();
+        // Mid-session, a late find is left in storage for the next launch
+        if saved.is_some() && self.state == AppState::Menu {
+            self.saved_session = saved;
+            self.session_stored = true;
+            self.state = AppState::ResumePrompt;
+        }
+        self.apply_pending_settings();
+    }
+
+    /// Apply the settings `load_stored` found, once no session is running
+    /// that they could change under.
+    fn apply_pending_settings(&mut self) {
+        if !matches!(self.state, AppState::Menu | AppState::ResumePrompt | AppState::ConfirmDiscard) {
+            return;
+        }
+        if let Some(settings) = self.pending_settings.take() {
+            if SESSION_LENGTHS.contains(&settings.session_length) {
+                self.session_length = settings.session_length;
+            }
+            self.session_kind = match settings.session_kind {
+                SessionKind::Sprint(secs) if !SPRINT_SECS.contains(&secs) => {
+                    SessionKind::Sprint(DEFAULT_SPRINT_SECS)
+                }
+                kind => kind,
+            };
+            for (limit, saved) in self.problem_limit_secs.iter_mut().zip(settings.problem_limit_secs) {
+                if PROBLEM_LIMIT_SECS.contains(&saved) {
+                    *limit = saved;
+                }
+            }
+            // Bests are cached for the length and mode in use
+            self.refresh_best();
+        }
+    }
+
+    /// Finish loading from storage if it has mounted since launch.
+    fn load_if_mounted(&mut self) {
+        if !self.stored_loaded && self.storage.is_mounted() {
+            self.load_stored();
+            self.refresh_best();
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Whether scores are currently being persisted.
+    pub fn scores_saved(&self) -> bool {
+        self.storage.is_persistent() && !self.save_failed
+    }
+
+    /// Freeze a running session; called for the Menu key and when the app
+    /// loses focus. Does nothing outside Playing / Feedback.
+    pub fn pause(&mut self, now_ms: u64) {
+        if matches!(self.state, AppState::Playing | AppState::Feedback) {
+            self.paused_from = self.state;
+            self.paused_at = now_ms;
+            self.state = AppState::Paused;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Pick up where `pause` left off, pushing every session timer back by
+    /// the time spent paused.
+    fn resume(&mut self, now_ms: u64) {
+        let paused_for = now_ms.saturating_sub(self.paused_at);
+        self.problem_start_ms += paused_for;
+        self.feedback_timer += paused_for;
+        self.sprint_end_ms += paused_for;
+        if let Some(at) = self.first_key_at.as_mut() {
+            *at += paused_for;
+        }
+        self.state = self.paused_from;
+    }
+
+    /// Leave a session early: Zen ends (it has no other way to), a retry
+    /// round returns to its results, anything else is abandoned.
+    fn leave_session(&mut self) {
+        if self.retry_round > 0 {
+            self.retry_round = 0;
+            self.retry_queue.clear();
+            self.state = AppState::Results;
+        } else if self.session_kind == SessionKind::Zen && !self.session_problems.is_empty() {
+            self.finish_session();
+        } else {
+            self.abandon_session();
+        }
+    }
+
+    /// Whether leaving the session from Paused would throw it away, rather
+    /// than finishing it (Zen) or returning to Results (a retry round).
+    fn leaving_abandons(&self) -> bool {
+        self.retry_round == 0 && self.session_kind != SessionKind::Zen
+    }
+
+    /// Log a session that was given up on as incomplete, then go to Menu.
+    fn abandon_session(&mut self) {
+        self.discard_saved_session();
+        if !self.session_problems.is_empty() {
+            let record = self.session_record(true);
+            if self.storage.append_history(&record).is_err() {
+                self.save_failed = true;
+            }
+            if self.storage.save_facts(&mut self.facts).is_err() {
+                self.save_failed = true;
+            }
+        }
+        self.state = AppState::Menu;
+    }
+
+    fn session_record(&self, incomplete: bool) -> SessionRecord {
+        SessionRecord {
+            timestamp: unix_time_secs(),
+            op_mode: self.op_mode,
+            difficulty: self.difficulty,
+            seed: self.session_rng.seed(),
+            kind: self.session_kind,
+            incomplete,
+            attempts: self.session_problems.clone(),
         }
     }
 
+    /// Called when the app loses focus or quits. A paused session is saved
+    /// so it can be resumed on the next launch; anything else clears it.
     pub fn save_state(&mut self) {
-        // Stats are saved at end of session in handle_results
+        self.load_if_mounted();
+        if matches!(self.state, AppState::ResumePrompt | AppState::ConfirmDiscard) {
+            // Still undecided; keep the saved session for next time
+            return;
+        }
+        if !matches!(self.state, AppState::Paused | AppState::ConfirmAbandon) {
+            self.discard_saved_session();
+            return;
+        }
+        let snapshot = self.snapshot();
+        let mut saved = self.storage.save_session_in_progress(&snapshot).is_ok();
+        // Keep the facts answered so far even if the session is never resumed
+        saved &= self.storage.save_facts(&mut self.facts).is_ok();
+        self.session_stored = true;
+        self.save_failed |= !saved;
     }
 
-    fn next_problem(&mut self, rng: &Rng) {
-        let problem = match self.op_mode {
-            OpMode::Single(op) => generate(rng, op, self.difficulty),
-            OpMode::Mixed => generate_mixed(rng, self.difficulty),
+    fn discard_saved_session(&mut self) {
+        if self.session_stored {
+            self.session_stored = self.storage.clear_session_in_progress().is_err();
+        }
+    }
+
+    /// The paused session, with times made relative to the pause.
+    fn snapshot(&self) -> SavedSession {
+        let at = self.paused_at;
+        SavedSession {
+            op_mode: self.op_mode,
+            difficulty: self.difficulty,
+            session_length: self.session_length,
+            session_kind: self.session_kind,
+            problem_limit_secs: self.problem_limit_secs,
+            find_operand: self.find_operand,
+            negatives: self.negatives,
+            seed: self.session_rng.seed(),
+            rng_state: self.session_rng.state(),
+            state: self.paused_from,
+            current_problem: self.current_problem.clone(),
+            answer_buffer: self.answer_buffer.clone(),
+            problem_num: self.problem_num,
+            correct_count: self.correct_count,
+            streak: self.streak,
+            best_streak: self.best_streak,
+            lives: self.lives,
+            total_time_ms: self.total_time_ms,
+            problem_elapsed_ms: at.saturating_sub(self.problem_start_ms),
+            first_key_elapsed_ms: self.first_key_at.map(|key_at| key_at.saturating_sub(self.problem_start_ms)),
+            backspaces: self.backspaces,
+            sprint_left_ms: self.sprint_end_ms.saturating_sub(at),
+            feedback_correct: self.feedback_correct,
+            feedback_problem: self.feedback_problem.clone(),
+            feedback_user_input: self.feedback_user_input.clone(),
+            feedback_timed_out: self.feedback_timed_out,
+            feedback_elapsed_ms: at.saturating_sub(self.feedback_timer),
+            session_problems: self.session_problems.clone(),
+            retry_round: self.retry_round,
+            retry_queue: self.retry_queue.clone(),
+            retry_missed: self.retry_missed.clone(),
+        }
+    }
+
+    /// Continue a saved session as if it had been paused at `now_ms`.
+    fn restore(&mut self, saved: SavedSession, now_ms: u64) {
+        self.op_mode = saved.op_mode;
+        self.difficulty = saved.difficulty;
+        self.session_length = saved.session_length;
+        self.session_kind = saved.session_kind;
+        self.problem_limit_secs = saved.problem_limit_secs;
+        self.find_operand = saved.find_operand;
+        self.negatives = saved.negatives;
+        self.session_rng = SeededRng::resume(saved.seed, saved.rng_state);
+        self.current_problem = saved.current_problem;
+        self.answer_buffer = saved.answer_buffer;
+        self.problem_num = saved.problem_num;
+        self.correct_count = saved.correct_count;
+        self.streak = saved.streak;
+        self.best_streak = saved.best_streak;
+        self.lives = saved.lives;
+        self.total_time_ms = saved.total_time_ms;
+        // Soon after a reboot `now_ms` may be smaller than the time already
+        // spent; the problem then just loses the difference.
+        self.problem_start_ms = now_ms.saturating_sub(saved.problem_elapsed_ms);
+        self.first_key_at = saved.first_key_elapsed_ms.map(|ms| self.problem_start_ms + ms);
+        self.backspaces = saved.backspaces;
+        self.sprint_end_ms = now_ms + saved.sprint_left_ms;
+        self.feedback_correct = saved.feedback_correct;
+        self.feedback_problem = saved.feedback_problem;
+        self.feedback_user_input = saved.feedback_user_input;
+        self.feedback_timed_out = saved.feedback_timed_out;
+        self.feedback_timer = now_ms.saturating_sub(saved.feedback_elapsed_ms);
+        self.session_problems = saved.session_problems;
+        self.retry_round = saved.retry_round;
+        self.retry_queue = saved.retry_queue;
+        self.retry_missed = saved.retry_missed;
+        self.retry_cleared = None;
+        self.save_failed = false;
+        // The menu now shows the session's length and limits; keep them
+        self.save_settings();
+        self.facts.session = self.storage.history_count();
+        self.now_ms = now_ms;
+        self.paused_from = saved.state;
+        self.paused_at = now_ms;
+        self.resume(now_ms);
+    }
+
+    fn next_problem(&mut self) {
+        let rng = &self.session_rng;
+        let level = match self.session_kind {
+            SessionKind::Survival => self.survival_level(),
+            _ => 0,
+        };
+        let mut problem = match (self.session_kind, self.op_mode) {
+            // Signed operands escalate in Survival like unsigned ones
+            (_, OpMode::Single(op)) if self.negatives && op.is_arithmetic() => {
+                generate_signed(rng, op, self.difficulty.operand_range_at(level))
+            }
+            (_, OpMode::Mixed) if self.negatives => {
+                generate_signed_mixed(rng, self.difficulty.operand_range_at(level))
+            }
+            (SessionKind::Survival, OpMode::Single(op)) => {
+                generate_in_range(rng, op, self.difficulty.operand_range_at(level))
+            }
+            (SessionKind::Survival, OpMode::Expressions) => {
+                generate_expression_in_range(rng, self.difficulty.operand_range_at(level))
+            }
+            // Fractions and decimals get harder by difficulty only
+            (_, OpMode::Fractions) => generate_fraction_mixed(rng, self.difficulty),
+            (_, OpMode::Decimals) => generate_decimal_mixed(rng, self.difficulty),
+            // Practice facts don't escalate, so a Practice run draws from every operation
+            (SessionKind::Survival, _) => generate_mixed_in_range(rng, self.difficulty.operand_range_at(level)),
+            (_, OpMode::Single(op)) => generate(rng, op, self.difficulty),
+            (_, OpMode::Mixed) => generate_mixed(rng, self.difficulty),
+            (_, OpMode::Expressions) => generate_expression(rng, self.difficulty),
+            (_, OpMode::Practice) => {
+                self.facts
+                    .practice_problem(rng, self.difficulty, self.current_problem.as_ref())
+            }
         };
+        // Only a plain two-operand problem can have a blank operand
+        if self.find_operand && problem.is_fact() {
+            problem.blank = if rng.range(2) == 0 { Blank::Left } else { Blank::Right };
+            if !problem.has_unique_blank() {
+                problem.blank = Blank::Answer;
+            }
+        }
         self.current_problem = Some(problem);
+        self.reset_answer();
+    }
+
+    fn reset_answer(&mut self) {
         self.answer_buffer.clear();
+        self.first_key_at = None;
+        self.backspaces = 0;
+    }
+
+    /// How far a Survival run has escalated, from 0.
+    pub fn survival_level(&self) -> u32 {
+        (self.problem_num / SURVIVAL_LEVEL_EVERY) as u32
     }
 
-    fn start_session(&mut self, rng: &Rng, now_ms: u64) {
+    fn start_session<R: RandomSource>(&mut self, rng: &R, now_ms: u64) {
+        self.start_session_with_seed(rng.u64(), now_ms);
+    }
+
+    /// Start a session whose problems are fully determined by `seed`.
+    pub fn start_session_with_seed(&mut self, seed: u64, now_ms: u64) {
+        self.session_rng = SeededRng::new(seed);
+        log::info!("session seed {:#018x}", self.session_rng.seed());
         self.problem_num = 0;
         self.correct_count = 0;
         self.streak = 0;
         self.best_streak = 0;
         self.total_time_ms = 0;
+        self.lives = SURVIVAL_LIVES;
         self.session_problems.clear();
+        self.retry_round = 0;
+        self.retry_cleared = None;
+        self.save_failed = false;
+        self.facts.session = self.storage.history_count();
+        if let SessionKind::Sprint(secs) = self.session_kind {
+            self.sprint_end_ms = now_ms + secs as u64 * 1000;
+        }
+        self.now_ms = now_ms;
         self.problem_start_ms = now_ms;
-        self.next_problem(rng);
+        self.next_problem();
         self.state = AppState::Playing;
     }
 
     fn submit_answer(&mut self, now_ms: u64) {
-        let user_answer = self.answer_buffer.trim().parse::<i32>().unwrap_or(i32::MIN);
+        let input = String::from(self.answer_buffer.trim());
+        self.record_attempt(Some(input), now_ms);
+    }
+
+    /// Score the current problem and show feedback; `None` means it timed out.
+    fn record_attempt(&mut self, input: Option<String>, now_ms: u64) {
         if let Some(ref problem) = self.current_problem {
-            let correct = problem.check(user_answer);
+            let correct = input.as_deref().is_some_and(|input| problem.check_input(input));
+            let elapsed = now_ms.saturating_sub(self.problem_start_ms);
+
+            let timed_out = input.is_none();
+            let input = input.unwrap_or_default();
+            let user_answer = if problem.has_integer_answer() { input.parse::<i32>().ok() } else { None };
+            self.feedback_correct = correct;
+            self.feedback_problem = Some(problem.clone());
+            self.feedback_user_input = input.clone();
+            self.feedback_timed_out = timed_out;
+            self.feedback_timer = now_ms;
+            self.state = AppState::Feedback;
+
+            // A retry round leaves the session's own score and the facts alone
+            if self.retry_round > 0 {
+                if !correct {
+                    self.retry_missed.push(problem.clone());
+                }
+                return;
+            }
+
+            self.facts.record(problem, correct, elapsed as u32);
+
             if correct {
                 self.correct_count += 1;
                 self.streak += 1;
@@ -159,111 +757,383 @@ impl MathDrillApp {
                 }
             } else {
                 self.streak = 0;
+                if self.session_kind == SessionKind::Survival {
+                    self.lives = self.lives.saturating_sub(1);
+                }
             }
 
-            let elapsed = now_ms.saturating_sub(self.problem_start_ms);
             self.total_time_ms += elapsed;
+            self.session_problems.push(Attempt {
+                problem: problem.clone(),
+                user_answer,
+                correct,
+                time_ms: elapsed as u32,
+                first_key_ms: self
+                    .first_key_at
+                    .map_or(elapsed, |at| at.saturating_sub(self.problem_start_ms))
+                    as u32,
+                backspaces: self.backspaces,
+                timed_out,
+                user_input: if user_answer.is_some() { String::new() } else { input },
+            });
+            self.problem_num += 1;
+        }
+    }
 
-            self.feedback_correct = correct;
-            self.feedback_problem = Some(problem.clone());
-            self.feedback_user_answer = user_answer;
-            self.session_problems.push((problem.clone(), user_answer, correct));
+    /// Whether the results screen should offer a retry of the missed problems.
+    pub fn can_retry(&self) -> bool {
+        self.session_kind != SessionKind::Zen
+            && self.retry_cleared.is_none()
+            && self.session_problems.iter().any(|a| !a.correct)
+    }
 
-            self.problem_num += 1;
-            self.feedback_timer = now_ms;
-            self.state = AppState::Feedback;
+    /// Start a follow-up round of exactly the session's missed problems.
+    fn start_retry(&mut self, now_ms: u64) {
+        self.retry_missed = self
+            .session_problems
+            .iter()
+            .filter(|a| !a.correct)
+            .map(|a| a.problem.clone())
+            .collect();
+        self.retry_round = 0;
+        self.next_retry_problem(now_ms);
+    }
+
+    /// Ask the next retry problem. When a pass runs out its misses become
+    /// the next pass, shuffled; a pass without misses ends the round.
+    fn next_retry_problem(&mut self, now_ms: u64) {
+        if self.retry_queue.is_empty() {
+            if self.retry_missed.is_empty() {
+                self.retry_cleared = Some(self.retry_round);
+                self.retry_round = 0;
+                self.state = AppState::Results;
+                return;
+            }
+            self.retry_round += 1;
+            self.retry_queue = core::mem::take(&mut self.retry_missed);
+            shuffle(&self.session_rng, &mut self.retry_queue);
         }
+        self.current_problem = self.retry_queue.pop();
+        self.reset_answer();
+        self.problem_start_ms = now_ms;
+        self.state = AppState::Playing;
     }
 
-    /// Called from main loop to advance feedback → next problem or results.
-    pub fn check_feedback_timeout(&mut self, now_ms: u64, rng: &Rng) {
-        if self.state == AppState::Feedback {
-            let elapsed = now_ms.saturating_sub(self.feedback_timer);
-            if elapsed >= 1500 {
-                if self.problem_num >= PROBLEMS_PER_SESSION {
+    /// Time limit set for the selected difficulty, in seconds (0 = none).
+    pub fn difficulty_limit_secs(&self) -> u32 {
+        self.problem_limit_secs[self.difficulty_index()]
+    }
+
+    fn difficulty_index(&self) -> usize {
+        Difficulty::all()
+            .iter()
+            .position(|d| *d == self.difficulty)
+            .unwrap_or(0)
+    }
+
+    /// Time allowed for each problem, if the session has a limit.
+    pub fn problem_limit_ms(&self) -> Option<u64> {
+        if self.retry_round > 0 {
+            return None;
+        }
+        match (self.session_kind, self.difficulty_limit_secs()) {
+            (SessionKind::Zen, _) => None,
+            (SessionKind::Survival, 0) => Some(SURVIVAL_PROBLEM_SECS as u64 * 1000),
+            (_, 0) => None,
+            (_, secs) => Some(secs as u64 * 1000),
+        }
+    }
+
+    /// Time left on the current problem's limit, as of `now_ms`.
+    pub fn problem_remaining_ms(&self) -> Option<u64> {
+        self.problem_limit_ms()
+            .map(|limit| (self.problem_start_ms + limit).saturating_sub(self.now_ms))
+    }
+
+    /// Earliest time (ticktimer ms) at which the app wants `tick` to run,
+    /// or `None` if nothing is pending in the current state.
+    pub fn next_deadline(&self) -> Option<u64> {
+        let feedback = match self.state {
+            AppState::Feedback => Some(self.feedback_timer + FEEDBACK_MS),
+            _ => None,
+        };
+        let limit = match (self.state, self.problem_limit_ms()) {
+            // Each redraw step of the shrinking bar, ending at the limit itself
+            (AppState::Playing, Some(limit)) => {
+                let elapsed = self.now_ms.saturating_sub(self.problem_start_ms);
+                let step = (elapsed / LIMIT_BAR_STEP_MS + 1) * LIMIT_BAR_STEP_MS;
+                Some(self.problem_start_ms + step.min(limit))
+            }
+            _ => None,
+        };
+        let countdown = match (self.state, self.session_kind) {
+            (AppState::Playing | AppState::Feedback, SessionKind::Sprint(_)) if self.retry_round == 0 => {
+                // Wake on each whole second so the header countdown ticks
+                let remaining = self.sprint_remaining_ms();
+                Some(self.sprint_end_ms - remaining.saturating_sub(1) / 1000 * 1000)
+            }
+            _ => None,
+        };
+        [feedback, limit, countdown].into_iter().flatten().min()
+    }
+
+    /// Called from main loop on `AppOp::Tick` to advance timed states.
+    pub fn tick(&mut self, now_ms: u64) {
+        self.now_ms = now_ms;
+        self.load_if_mounted();
+        if let SessionKind::Sprint(_) = self.session_kind {
+            if matches!(self.state, AppState::Playing | AppState::Feedback) && self.retry_round == 0 {
+                self.needs_redraw = true;
+                if now_ms >= self.sprint_end_ms {
+                    // Out of time: an unanswered problem simply doesn't count
                     self.finish_session();
-                } else {
-                    self.problem_start_ms = now_ms;
-                    self.next_problem(rng);
-                    self.state = AppState::Playing;
-                    self.needs_redraw = true;
+                    return;
                 }
             }
         }
+        if let Some(limit) = self.problem_limit_ms() {
+            if self.state == AppState::Playing {
+                self.needs_redraw = true;
+                if now_ms >= self.problem_start_ms + limit {
+                    self.record_attempt(None, now_ms);
+                    return;
+                }
+            }
+        }
+        self.check_feedback_timeout(now_ms);
     }
 
-    fn finish_session(&mut self) {
-        // Check if this is a new best
-        if let Some(ref mut st) = self.storage {
-            let total = self.session_problems.len() as u32;
-            let avg_ms = if total > 0 {
-                (self.total_time_ms / total as u64) as u32
-            } else {
-                0
-            };
+    /// Time left in the current Sprint, as of `now_ms`.
+    pub fn sprint_remaining_ms(&self) -> u64 {
+        self.sprint_end_ms.saturating_sub(self.now_ms)
+    }
 
-            let is_new_best = match st.load_best(&self.difficulty) {
-                Some(prev) => {
-                    self.correct_count > prev.correct
-                        || (self.correct_count == prev.correct && avg_ms < prev.avg_ms)
-                        || self.best_streak > prev.streak
-                }
-                None => true,
-            };
+    fn session_over(&self, now_ms: u64) -> bool {
+        match self.session_kind {
+            SessionKind::Standard => self.problem_num >= self.session_length,
+            SessionKind::Sprint(_) => now_ms >= self.sprint_end_ms,
+            SessionKind::Survival => self.lives == 0,
+            SessionKind::Zen => false,
+        }
+    }
 
-            if is_new_best {
-                let new_best = BestStats {
-                    streak: self.best_streak,
-                    correct: self.correct_count,
-                    total,
-                    avg_ms,
-                };
-                st.save_best(&self.difficulty, &new_best);
+    /// Advance feedback → next problem or results once the timer expires.
+    pub fn check_feedback_timeout(&mut self, now_ms: u64) {
+        if self.state == AppState::Feedback {
+            let elapsed = now_ms.saturating_sub(self.feedback_timer);
+            if elapsed >= FEEDBACK_MS {
+                self.needs_redraw = true;
+                self.leave_feedback(now_ms);
             }
         }
+    }
+
+    /// Move on from feedback: finish, retry a Zen miss, or the next problem.
+    fn leave_feedback(&mut self, now_ms: u64) {
+        if self.retry_round > 0 {
+            self.next_retry_problem(now_ms);
+            return;
+        }
+        if self.session_over(now_ms) {
+            self.finish_session();
+            return;
+        }
+        if self.session_kind == SessionKind::Zen && !self.feedback_correct {
+            self.reset_answer();
+        } else {
+            self.next_problem();
+        }
+        self.problem_start_ms = now_ms;
+        self.state = AppState::Playing;
+    }
+
+    fn finish_session(&mut self) {
+        self.discard_saved_session();
+        self.record_best();
+
+        let record = self.session_record(false);
+        if self.storage.append_history(&record).is_err() {
+            self.save_failed = true;
+        }
+        if self.storage.save_facts(&mut self.facts).is_err() {
+            self.save_failed = true;
+        }
 
         self.state = AppState::Results;
     }
 
-    pub fn handle_key(&mut self, key: char, now_ms: u64, rng: &Rng) -> bool {
+    /// Save the session's result if it beats the best for its difficulty
+    /// and length (or Sprint duration). Zen sessions never touch the bests.
+    fn record_best(&mut self) {
+        let total = self.session_problems.len() as u32;
+        let avg_ms = self.avg_time_ms();
+        let prev = match self.session_kind {
+            SessionKind::Standard => self.storage.load_best(&self.difficulty, self.session_length),
+            SessionKind::Sprint(secs) => self.storage.load_sprint_best(&self.difficulty, secs),
+            SessionKind::Survival => self.storage.load_survival_best(&self.difficulty),
+            SessionKind::Zen => None,
+        };
+
+        let is_new_best = match (prev, self.session_kind) {
+            // Zen is unscored
+            (_, SessionKind::Zen) => false,
+            (None, _) => true,
+            (Some(prev), SessionKind::Standard) => {
+                self.correct_count > prev.correct
+                    || (self.correct_count == prev.correct && avg_ms < prev.avg_ms)
+                    || self.best_streak > prev.streak
+            }
+            // Most correct in the time, then fewest misses
+            (Some(prev), SessionKind::Sprint(_)) => {
+                self.correct_count > prev.correct
+                    || (self.correct_count == prev.correct && total < prev.total)
+            }
+            // Longest run, then fastest
+            (Some(prev), SessionKind::Survival) => {
+                self.correct_count > prev.correct
+                    || (self.correct_count == prev.correct && avg_ms < prev.avg_ms)
+            }
+        };
+
+        if is_new_best {
+            let new_best = BestStats {
+                streak: self.best_streak,
+                correct: self.correct_count,
+                total,
+                avg_ms,
+            };
+            let result = match self.session_kind {
+                SessionKind::Standard => {
+                    self.storage.save_best(&self.difficulty, self.session_length, &new_best)
+                }
+                SessionKind::Sprint(secs) => {
+                    self.storage.save_sprint_best(&self.difficulty, secs, &new_best)
+                }
+                SessionKind::Survival => self.storage.save_survival_best(&self.difficulty, &new_best),
+                SessionKind::Zen => Ok(()),
+            };
+            self.save_failed |= result.is_err();
+        }
+    }
+
+    pub fn handle_key<R: RandomSource>(&mut self, key: char, now_ms: u64, rng: &R) -> bool {
         self.needs_redraw = true;
-        match self.state {
+        self.now_ms = now_ms;
+        let state = self.state;
+        self.load_if_mounted();
+        if self.state != state {
+            // The key that found the PDDB mounted only brings up the resume prompt
+            return true;
+        }
+        let keep_running = match self.state {
+            AppState::ResumePrompt => self.handle_resume_prompt(key, now_ms),
+            AppState::ConfirmDiscard => self.handle_confirm_discard(key),
             AppState::Menu => self.handle_menu(key, now_ms, rng),
             AppState::Playing => self.handle_playing(key, now_ms),
-            AppState::Feedback => self.handle_feedback(key, now_ms, rng),
-            AppState::Results => self.handle_results(key),
+            AppState::Feedback => self.handle_feedback(key, now_ms),
+            AppState::Paused => self.handle_paused(key, now_ms),
+            AppState::ConfirmAbandon => self.handle_confirm_abandon(key),
+            AppState::Results => self.handle_results(key, now_ms),
             AppState::BestScores => self.handle_best_scores(key),
+            AppState::Heatmap => self.handle_heatmap(key),
+        };
+        // Settings found by a mount mid-session wait for the way back to Menu
+        self.apply_pending_settings();
+        keep_running
+    }
+
+    fn handle_resume_prompt(&mut self, key: char, now_ms: u64) -> bool {
+        match key {
+            KEY_ENTER | ' ' => {
+                match self.saved_session.take() {
+                    Some(saved) if saved_settings_valid(&saved) => {
+                        self.restore(saved, now_ms);
+                    }
+                    _ => {
+                        // Corrupt or truncated; nothing safe to resume
+                        self.discard_saved_session();
+                        self.state = AppState::Menu;
+                    }
+                }
+            }
+            KEY_MENU => {
+                self.state = AppState::ConfirmDiscard;
+            }
+            _ => {}
         }
+        true
+    }
+
+    fn handle_confirm_discard(&mut self, key: char) -> bool {
+        match key {
+            KEY_ENTER => {
+                if let Some(saved) = self.saved_session.take() {
+                    self.log_discarded(saved);
+                }
+                self.discard_saved_session();
+                self.state = AppState::Menu;
+            }
+            KEY_MENU => self.state = AppState::ResumePrompt,
+            _ => {}
+        }
+        true
     }
 
-    fn handle_menu(&mut self, key: char, now_ms: u64, rng: &Rng) -> bool {
+    /// Log a saved session that won't be resumed as incomplete, as
+    /// `abandon_session` would have. A retry round's session is already
+    /// logged.
+    fn log_discarded(&mut self, saved: SavedSession) {
+        if saved.retry_round > 0 || saved.session_problems.is_empty() {
+            return;
+        }
+        let record = SessionRecord {
+            timestamp: unix_time_secs(),
+            op_mode: saved.op_mode,
+            difficulty: saved.difficulty,
+            seed: saved.seed,
+            kind: saved.session_kind,
+            incomplete: true,
+            attempts: saved.session_problems,
+        };
+        if self.storage.append_history(&record).is_err() {
+            self.save_failed = true;
+        }
+    }
+
+    fn handle_menu<R: RandomSource>(&mut self, key: char, now_ms: u64, rng: &R) -> bool {
         match key {
             KEY_MENU => return false,
             KEY_UP => {
                 self.menu_field = match self.menu_field {
                     MenuField::Operation => MenuField::BestScores,
                     MenuField::Difficulty => MenuField::Operation,
-                    MenuField::Start => MenuField::Difficulty,
-                    MenuField::BestScores => MenuField::Start,
+                    MenuField::Find => MenuField::Difficulty,
+                    MenuField::Negatives => MenuField::Find,
+                    MenuField::Mode => MenuField::Negatives,
+                    MenuField::Length => MenuField::Mode,
+                    MenuField::TimeLimit => MenuField::Length,
+                    MenuField::Start => MenuField::TimeLimit,
+                    MenuField::Heatmap => MenuField::Start,
+                    MenuField::BestScores => MenuField::Heatmap,
                 };
             }
             KEY_DOWN => {
                 self.menu_field = match self.menu_field {
                     MenuField::Operation => MenuField::Difficulty,
-                    MenuField::Difficulty => MenuField::Start,
-                    MenuField::Start => MenuField::BestScores,
+                    MenuField::Difficulty => MenuField::Find,
+                    MenuField::Find => MenuField::Negatives,
+                    MenuField::Negatives => MenuField::Mode,
+                    MenuField::Mode => MenuField::Length,
+                    MenuField::Length => MenuField::TimeLimit,
+                    MenuField::TimeLimit => MenuField::Start,
+                    MenuField::Start => MenuField::Heatmap,
+                    MenuField::Heatmap => MenuField::BestScores,
                     MenuField::BestScores => MenuField::Operation,
                 };
             }
             KEY_LEFT | KEY_RIGHT => match self.menu_field {
                 MenuField::Operation => {
-                    self.op_mode = match self.op_mode {
-                        OpMode::Mixed => OpMode::Single(Operation::Add),
-                        OpMode::Single(Operation::Add) => OpMode::Single(Operation::Subtract),
-                        OpMode::Single(Operation::Subtract) => OpMode::Single(Operation::Multiply),
-                        OpMode::Single(Operation::Multiply) => OpMode::Single(Operation::Divide),
-                        OpMode::Single(Operation::Divide) => OpMode::Mixed,
-                    };
+                    self.cycle_op_mode(key == KEY_RIGHT);
                 }
                 MenuField::Difficulty => {
                     self.difficulty = match self.difficulty {
@@ -272,24 +1142,37 @@ impl MathDrillApp {
                         Difficulty::Hard => Difficulty::Easy,
                     };
                 }
+                MenuField::Find if self.op_mode.allows_blank() => {
+                    self.find_operand = !self.find_operand;
+                }
+                MenuField::Negatives if self.op_mode.allows_negatives() => {
+                    self.negatives = !self.negatives;
+                }
+                MenuField::Mode => {
+                    self.cycle_session_kind(key == KEY_RIGHT);
+                }
+                MenuField::Length => {
+                    self.cycle_session_length(key == KEY_RIGHT);
+                }
+                MenuField::TimeLimit => {
+                    self.cycle_problem_limit(key == KEY_RIGHT);
+                }
                 _ => {}
             },
             KEY_ENTER => match self.menu_field {
                 MenuField::Start => {
                     self.start_session(rng, now_ms);
                 }
+                MenuField::Heatmap => {
+                    self.state = AppState::Heatmap;
+                }
                 MenuField::BestScores => {
+                    self.refresh_best();
                     self.state = AppState::BestScores;
                 }
                 MenuField::Operation => {
                     // Also cycle on Enter
-                    self.op_mode = match self.op_mode {
-                        OpMode::Mixed => OpMode::Single(Operation::Add),
-                        OpMode::Single(Operation::Add) => OpMode::Single(Operation::Subtract),
-                        OpMode::Single(Operation::Subtract) => OpMode::Single(Operation::Multiply),
-                        OpMode::Single(Operation::Multiply) => OpMode::Single(Operation::Divide),
-                        OpMode::Single(Operation::Divide) => OpMode::Mixed,
-                    };
+                    self.cycle_op_mode(true);
                 }
                 MenuField::Difficulty => {
                     self.difficulty = match self.difficulty {
@@ -298,59 +1181,158 @@ impl MathDrillApp {
                         Difficulty::Hard => Difficulty::Easy,
                     };
                 }
+                MenuField::Find if self.op_mode.allows_blank() => {
+                    self.find_operand = !self.find_operand;
+                }
+                MenuField::Negatives if self.op_mode.allows_negatives() => {
+                    self.negatives = !self.negatives;
+                }
+                MenuField::Mode => {
+                    self.cycle_session_kind(true);
+                }
+                MenuField::Length => {
+                    self.cycle_session_length(true);
+                }
+                MenuField::TimeLimit => {
+                    self.cycle_problem_limit(true);
+                }
+                MenuField::Find | MenuField::Negatives => {}
             },
             _ => {}
         }
         true
     }
 
+    /// Step to the next (or previous) operation mode in menu order.
+    fn cycle_op_mode(&mut self, forward: bool) {
+        self.op_mode = step(&OP_MODES, self.op_mode, forward);
+    }
+
+    /// Step to the next (or previous) session mode and save it.
+    fn cycle_session_kind(&mut self, forward: bool) {
+        let current = match self.session_kind {
+            SessionKind::Sprint(_) => SessionKind::Sprint(DEFAULT_SPRINT_SECS),
+            kind => kind,
+        };
+        self.session_kind = step(&SESSION_KINDS, current, forward);
+        self.save_settings();
+    }
+
+    /// Step to the next (or previous) session length — problem count, or
+    /// seconds for a Sprint — and save it. Survival and Zen have no length.
+    fn cycle_session_length(&mut self, forward: bool) {
+        match self.session_kind {
+            SessionKind::Standard => {
+                self.session_length = step(&SESSION_LENGTHS, self.session_length, forward);
+            }
+            SessionKind::Sprint(secs) => {
+                self.session_kind = SessionKind::Sprint(step(&SPRINT_SECS, secs, forward));
+            }
+            SessionKind::Survival | SessionKind::Zen => return,
+        }
+        self.save_settings();
+    }
+
+    /// Step the selected difficulty's per-problem time limit and save it.
+    fn cycle_problem_limit(&mut self, forward: bool) {
+        let i = self.difficulty_index();
+        self.problem_limit_secs[i] = step(&PROBLEM_LIMIT_SECS, self.problem_limit_secs[i], forward);
+        self.save_settings();
+    }
+
+    fn save_settings(&mut self) {
+        let settings = Settings {
+            session_length: self.session_length,
+            session_kind: self.session_kind,
+            problem_limit_secs: self.problem_limit_secs,
+        };
+        self.settings_unsaved = self.storage.save_settings(&settings).is_err();
+        self.save_failed |= self.settings_unsaved;
+    }
+
     fn handle_playing(&mut self, key: char, now_ms: u64) -> bool {
+        // `a/b` and mixed numbers `w a/b` for fraction answers, a point for decimals
+        let fraction = self
+            .current_problem
+            .as_ref()
+            .is_some_and(|p| matches!(p.kind, ProblemKind::Fraction(_)));
+        let decimal = self.current_problem.as_ref().is_some_and(|p| p.decimal_answer().is_some());
+        let max_len = if fraction { fraction::MAX_ANSWER_LEN } else { 8 };
+        let after_digit = self.answer_buffer.ends_with(|c: char| c.is_ascii_digit());
+        let separator = match key {
+            '/' => fraction && after_digit && !self.answer_buffer.contains('/'),
+            ' ' => fraction && after_digit && !self.answer_buffer.contains([' ', '/']),
+            '.' => decimal && !self.answer_buffer.contains('.'),
+            _ => false,
+        };
+        let len = self.answer_buffer.len();
         match key {
             KEY_MENU => {
-                self.state = AppState::Menu;
+                self.pause(now_ms);
             }
-            KEY_BACKSPACE => {
+            KEY_BACKSPACE if !self.answer_buffer.is_empty() => {
                 self.answer_buffer.pop();
+                self.backspaces += 1;
             }
-            KEY_ENTER => {
-                if !self.answer_buffer.is_empty() {
-                    self.submit_answer(now_ms);
-                }
+            KEY_ENTER if !self.answer_buffer.is_empty() => {
+                self.submit_answer(now_ms);
             }
-            '-' => {
-                if self.answer_buffer.is_empty() {
-                    self.answer_buffer.push('-');
-                }
+            '-' if self.answer_buffer.is_empty() => {
+                self.answer_buffer.push('-');
             }
-            c @ '0'..='9' => {
-                if self.answer_buffer.len() < 8 {
-                    self.answer_buffer.push(c);
-                }
+            c @ '0'..='9' if self.answer_buffer.len() < max_len => {
+                self.answer_buffer.push(c);
+            }
+            c @ ('/' | ' ' | '.') if separator && self.answer_buffer.len() < max_len => {
+                self.answer_buffer.push(c);
             }
             _ => {}
         }
+        // Entry starts with the first key that changes the answer
+        if key != KEY_ENTER && self.answer_buffer.len() != len {
+            self.first_key_at.get_or_insert(now_ms);
+        }
         true
     }
 
-    fn handle_feedback(&mut self, key: char, now_ms: u64, rng: &Rng) -> bool {
-        // Any key skips the feedback timer
-        if key == KEY_ENTER || key == ' ' {
-            if self.problem_num >= PROBLEMS_PER_SESSION {
-                self.finish_session();
-            } else {
-                self.problem_start_ms = now_ms;
-                self.next_problem(rng);
-                self.state = AppState::Playing;
-            }
+    fn handle_feedback(&mut self, key: char, now_ms: u64) -> bool {
+        match key {
+            // Enter or Space skips the feedback timer
+            KEY_ENTER | ' ' => self.leave_feedback(now_ms),
+            KEY_MENU => self.pause(now_ms),
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_paused(&mut self, key: char, now_ms: u64) -> bool {
+        match key {
+            KEY_ENTER | ' ' => self.resume(now_ms),
+            KEY_MENU if self.leaving_abandons() => self.state = AppState::ConfirmAbandon,
+            KEY_MENU => self.leave_session(),
+            _ => {}
         }
         true
     }
 
-    fn handle_results(&mut self, key: char) -> bool {
+    fn handle_confirm_abandon(&mut self, key: char) -> bool {
+        match key {
+            KEY_ENTER => self.abandon_session(),
+            // Back to the pause screen, timers still frozen
+            KEY_MENU => self.state = AppState::Paused,
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_results(&mut self, key: char, now_ms: u64) -> bool {
         match key {
             KEY_ENTER | KEY_MENU | ' ' => {
                 self.state = AppState::Menu;
             }
+            'r' | 'R' if self.can_retry() => {
+                self.start_retry(now_ms);
+            }
             _ => {}
         }
         true
@@ -366,21 +1348,70 @@ impl MathDrillApp {
         true
     }
 
-    /// Load best stats for display.
-    pub fn get_best(&mut self, diff: &Difficulty) -> Option<BestStats> {
-        if let Some(ref mut st) = self.storage {
-            st.load_best(diff)
-        } else {
-            None
+    fn handle_heatmap(&mut self, key: char) -> bool {
+        match key {
+            KEY_MENU => {
+                self.state = AppState::Menu;
+            }
+            KEY_UP => self.heatmap_row = (self.heatmap_row + GRID_MAX - 2) % GRID_MAX + 1,
+            KEY_DOWN => self.heatmap_row = self.heatmap_row % GRID_MAX + 1,
+            KEY_LEFT => self.heatmap_col = (self.heatmap_col + GRID_MAX - 2) % GRID_MAX + 1,
+            KEY_RIGHT => self.heatmap_col = self.heatmap_col % GRID_MAX + 1,
+            KEY_ENTER => {
+                self.heatmap_op = match self.heatmap_op {
+                    Operation::Add => Operation::Subtract,
+                    Operation::Subtract => Operation::Multiply,
+                    Operation::Multiply => Operation::Divide,
+                    _ => Operation::Add,
+                };
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Reload `best_scores` from storage for the Best Scores screen.
+    pub fn refresh_best(&mut self) {
+        for (i, diff) in Difficulty::all().iter().enumerate() {
+            self.best_scores[i] = match self.session_kind {
+                SessionKind::Standard => self.storage.load_best(diff, self.session_length),
+                SessionKind::Sprint(secs) => self.storage.load_sprint_best(diff, secs),
+                SessionKind::Survival => self.storage.load_survival_best(diff),
+                SessionKind::Zen => None,
+            };
+        }
+        self.sessions_logged = self.storage.history_count();
+    }
+
+    /// Problems answered per minute over a Sprint, in tenths.
+    pub fn pace_per_min_x10(&self) -> u32 {
+        match self.session_kind {
+            SessionKind::Sprint(secs) => self.session_problems.len() as u32 * 600 / secs.max(1),
+            _ => 0,
         }
     }
 
     pub fn avg_time_ms(&self) -> u32 {
         let total = self.session_problems.len() as u64;
-        if total > 0 {
-            (self.total_time_ms / total) as u32
-        } else {
-            0
-        }
+        self.total_time_ms.checked_div(total).unwrap_or(0) as u32
     }
 }
+
+/// Whether a saved session's length, mode and time limits are ones the
+/// menu offers, as `apply_pending_settings` requires of stored settings.
+fn saved_settings_valid(saved: &SavedSession) -> bool {
+    let sprint_ok = match saved.session_kind {
+        SessionKind::Sprint(secs) => SPRINT_SECS.contains(&secs),
+        _ => true,
+    };
+    SESSION_LENGTHS.contains(&saved.session_length)
+        && sprint_ok
+        && saved.problem_limit_secs.iter().all(|secs| PROBLEM_LIMIT_SECS.contains(secs))
+}
+
+/// The entry after (or before) `current` in `options`, wrapping around.
+fn step<T: Copy + PartialEq>(options: &[T], current: T, forward: bool) -> T {
+    let n = options.len();
+    let i = options.iter().position(|&o| o == current).unwrap_or(0);
+    options[if forward { (i + 1) % n } else { (i + n - 1) % n }]
+}
@@ -0,0 +1,229 @@
+//! Fixed-point decimals for the decimal problems.
+//!
+//! A value is a whole number of units of 10^-places, so sums, differences
+//! and products are exact with no float rounding. Trailing zeros are
+//! dropped on construction, which makes `2.50` and `2.5` the same value.
+
+extern crate alloc;
+use alloc::format;
+use alloc::string::String;
+
+use serde::{Deserialize, Serialize};
+
+use crate::problems::{Difficulty, Operation};
+use crate::rng::RandomSource;
+
+/// Most decimal places an answer can need; also the most the parser takes.
+pub const MAX_PLACES: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decimal {
+    pub units: i64,
+    pub places: u32,
+}
+
+impl Decimal {
+    /// `units * 10^-places`, with trailing zeros dropped.
+    pub fn new(units: i64, places: u32) -> Self {
+        let (mut units, mut places) = (units, places);
+        while places > 0 && units % 10 == 0 {
+            units /= 10;
+            places -= 1;
+        }
+        Self { units, places }
+    }
+
+    /// Both values scaled to the same number of places.
+    fn aligned(a: Self, b: Self) -> (i64, i64, u32) {
+        let places = a.places.max(b.places);
+        (a.units * 10i64.pow(places - a.places), b.units * 10i64.pow(places - b.places), places)
+    }
+
+    /// `a op b`, or `None` if a quotient doesn't end within `MAX_PLACES` or
+    /// the operation is a power or root.
+    pub fn apply(op: Operation, a: Self, b: Self) -> Option<Self> {
+        match op {
+            Operation::Add => {
+                let (a, b, places) = Self::aligned(a, b);
+                Some(Self::new(a + b, places))
+            }
+            Operation::Subtract => {
+                let (a, b, places) = Self::aligned(a, b);
+                Some(Self::new(a - b, places))
+            }
+            Operation::Multiply => Some(Self::new(a.units * b.units, a.places + b.places)),
+            Operation::Percent => Some(Self::new(a.units * b.units, a.places + b.places + 2)),
+            Operation::Divide => {
+                let (a, b, _) = Self::aligned(a, b);
+                (0..=MAX_PLACES).find_map(|places| {
+                    let scaled = a * 10i64.pow(places);
+                    (b != 0 && scaled % b == 0).then(|| Self::new(scaled / b, places))
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// `3`, `0.25` or `-1.5`.
+    pub fn display(&self) -> String {
+        let scale = 10i64.pow(self.places);
+        let sign = if self.units < 0 { "-" } else { "" };
+        let (whole, frac) = (self.units.abs() / scale, self.units.abs() % scale);
+        if self.places == 0 {
+            format!("{}{}", sign, whole)
+        } else {
+            format!("{}{}.{:0width$}", sign, whole, frac, width = self.places as usize)
+        }
+    }
+
+    /// Parse a typed answer such as `2.5`, `2.50`, `.5` or `-3`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (sign, text) = match text.trim().strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, text.trim()),
+        };
+        let (whole, frac) = text.split_once('.').unwrap_or((text, ""));
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if whole.len() + frac.len() == 0 || !digits(whole) || !digits(frac) {
+            return None;
+        }
+        if frac.len() > MAX_PLACES as usize {
+            return None;
+        }
+        let units: i64 = format!("{}{}", whole, frac).parse().ok()?;
+        Some(Self::new(sign * units, frac.len() as u32))
+    }
+}
+
+/// A decimal problem: `a op b` with an exact decimal answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecimalProblem {
+    pub a: Decimal,
+    pub b: Decimal,
+    pub operation: Operation,
+    pub answer: Decimal,
+}
+
+impl DecimalProblem {
+    pub fn question(&self) -> String {
+        format!("{} {} {}", self.a.display(), self.operation.symbol(), self.b.display())
+    }
+}
+
+/// Decimal places for each operation at a difficulty.
+struct Places {
+    /// Largest whole part of an operand to add or subtract.
+    max_whole: i64,
+    /// Places of each addend, chosen from 1 to this.
+    add: u32,
+    /// Places of the two factors of a product.
+    mul: (u32, u32),
+    /// Places of the quotient and the divisor.
+    div: (u32, u32),
+}
+
+fn places(difficulty: Difficulty) -> Places {
+    match difficulty {
+        Difficulty::Easy => Places { max_whole: 9, add: 1, mul: (1, 0), div: (1, 0) },
+        Difficulty::Medium => Places { max_whole: 19, add: 2, mul: (1, 1), div: (1, 0) },
+        Difficulty::Hard => Places { max_whole: 49, add: 3, mul: (2, 1), div: (1, 1) },
+    }
+}
+
+/// A nonzero value with up to `max_whole` before the point and exactly
+/// `places` after it (the last one nonzero). Whole factors and divisors
+/// start at 2, so multiplying by them is never trivial.
+fn operand<R: RandomSource + ?Sized>(rng: &R, max_whole: i64, places: u32) -> Decimal {
+    if places == 0 {
+        return Decimal::new(rng.range_inclusive(2, max_whole as u32) as i64, 0);
+    }
+    let whole = rng.range_inclusive(0, max_whole as u32) as i64;
+    let scale = 10i64.pow(places);
+    let mut frac = rng.range_inclusive(1, (scale - 1) as u32) as i64;
+    if frac % 10 == 0 {
+        frac += 1;
+    }
+    Decimal::new(whole * scale + frac, places)
+}
+
+/// Generate `a op b`; a difference is never negative and a quotient is
+/// always exact.
+pub fn generate<R: RandomSource + ?Sized>(rng: &R, operation: Operation, difficulty: Difficulty) -> DecimalProblem {
+    let limits = places(difficulty);
+    // Products and quotients keep to single-digit whole parts
+    let (a, b) = match operation {
+        Operation::Add | Operation::Subtract => {
+            let mut a = operand(rng, limits.max_whole, rng.range_inclusive(1, limits.add));
+            let mut b = operand(rng, limits.max_whole, rng.range_inclusive(1, limits.add));
+            let (x, y, _) = Decimal::aligned(a, b);
+            if operation == Operation::Subtract && x < y {
+                core::mem::swap(&mut a, &mut b);
+            }
+            (a, b)
+        }
+        Operation::Divide => {
+            let answer = operand(rng, 9, limits.div.0);
+            let b = operand(rng, 9, limits.div.1);
+            (Decimal::apply(Operation::Multiply, answer, b).unwrap_or(answer), b)
+        }
+        _ => (operand(rng, 9, limits.mul.0), operand(rng, 9, limits.mul.1)),
+    };
+    // Only a quotient can fail, and `a` was built as a multiple of `b`
+    let answer = Decimal::apply(operation, a, b).unwrap_or(Decimal::new(0, 0));
+    DecimalProblem { a, b, operation, answer }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SeededRng;
+
+    fn d(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_is_exact() {
+        assert_eq!(Decimal::apply(Operation::Add, d("3.75"), d("1.4")), Some(d("5.15")));
+        assert_eq!(Decimal::apply(Operation::Add, d("0.1"), d("0.2")), Some(d("0.3")));
+        assert_eq!(Decimal::apply(Operation::Subtract, d("1.4"), d("3.75")), Some(d("-2.35")));
+        assert_eq!(Decimal::apply(Operation::Multiply, d("0.6"), d("0.3")), Some(d("0.18")));
+        assert_eq!(Decimal::apply(Operation::Divide, d("0.84"), d("0.4")), Some(d("2.1")));
+        assert_eq!(Decimal::apply(Operation::Divide, d("1"), d("3")), None);
+        assert_eq!(d("0.18").display(), "0.18");
+        assert_eq!(d("-2.05").display(), "-2.05");
+        assert_eq!(d("2.50").display(), "2.5");
+    }
+
+    #[test]
+    fn trailing_zeros_do_not_matter() {
+        assert_eq!(Decimal::parse("2.50"), Decimal::parse("2.5"));
+        assert_eq!(Decimal::parse("3.0"), Decimal::parse("3"));
+        assert_eq!(Decimal::parse(".5"), Decimal::parse("0.5"));
+        assert_eq!(Decimal::parse("5."), Decimal::parse("5"));
+        assert_eq!(Decimal::parse("."), None);
+        assert_eq!(Decimal::parse("1.2.3"), None);
+        assert_eq!(Decimal::parse("1-2"), None);
+    }
+
+    #[test]
+    fn generated_problems_check_out() {
+        let rng = SeededRng::new(22);
+        for &difficulty in Difficulty::all() {
+            let limits = places(difficulty);
+            for _ in 0..200 {
+                let op = Operation::all()[rng.range(4) as usize];
+                let p = generate(&rng, op, difficulty);
+                assert_eq!(Decimal::apply(op, p.a, p.b), Some(p.answer));
+                assert!(p.answer.units >= 0);
+                let most = match op {
+                    Operation::Add | Operation::Subtract => limits.add,
+                    Operation::Divide => limits.div.0,
+                    _ => limits.mul.0 + limits.mul.1,
+                };
+                assert!(p.answer.places <= most, "{} = {}", p.question(), p.answer.display());
+                assert_eq!(Decimal::parse(&p.answer.display()), Some(p.answer));
+            }
+        }
+    }
+}
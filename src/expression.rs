@@ -0,0 +1,284 @@
+//! Multi-operand expressions such as `3 + 4 x 5 - 2` or `(6 + 2) / 4`.
+//!
+//! An expression is a flat list of tokens, evaluated with the usual order
+//! of operations: parentheses first, then x and /, then + and -, each left
+//! to right. Generated expressions keep every intermediate result between
+//! 0 and a limit, and every division exact.
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::problems::{pick_op, Operation};
+use crate::rng::RandomSource;
+
+/// Fewest and most operands in a generated expression.
+pub const MIN_OPERANDS: u32 = 3;
+pub const MAX_OPERANDS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Token {
+    Num(i32),
+    Op(Operation),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Expression {
+    pub tokens: Vec<Token>,
+}
+
+impl Expression {
+    pub fn display(&self) -> String {
+        let mut out = String::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            let after_open = i > 0 && self.tokens[i - 1] == Token::Open;
+            if i > 0 && !after_open && *token != Token::Close {
+                out.push(' ');
+            }
+            match token {
+                Token::Num(n) => out.push_str(&alloc::format!("{}", n)),
+                Token::Op(op) => out.push_str(op.symbol()),
+                Token::Open => out.push('('),
+                Token::Close => out.push(')'),
+            }
+        }
+        out
+    }
+
+    /// Evaluate with the usual precedence, or `None` if the tokens are
+    /// malformed, a division isn't exact, or the arithmetic overflows.
+    #[cfg(test)]
+    pub fn eval(&self) -> Option<i32> {
+        Evaluator::new(&self.tokens, i32::MIN, i32::MAX).run()
+    }
+
+    /// As `eval`, but also `None` if any intermediate result leaves
+    /// `0..=limit`.
+    pub fn eval_within(&self, limit: i32) -> Option<i32> {
+        Evaluator::new(&self.tokens, 0, limit).run()
+    }
+}
+
+/// Recursive-descent evaluator:
+///   expr   := term (('+' | '-') term)*
+///   term   := factor (('x' | '/') factor)*
+///   factor := number | '(' expr ')'
+struct Evaluator<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    lo: i32,
+    hi: i32,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(tokens: &'a [Token], lo: i32, hi: i32) -> Self {
+        Self { tokens, pos: 0, lo, hi }
+    }
+
+    fn run(mut self) -> Option<i32> {
+        let value = self.expr()?;
+        (self.pos == self.tokens.len()).then_some(value)
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn checked(&self, value: i32) -> Option<i32> {
+        (self.lo..=self.hi).contains(&value).then_some(value)
+    }
+
+    fn expr(&mut self) -> Option<i32> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ (Operation::Add | Operation::Subtract))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = match op {
+                Operation::Add => value.checked_add(rhs)?,
+                _ => value.checked_sub(rhs)?,
+            };
+            value = self.checked(value)?;
+        }
+        Some(value)
+    }
+
+    fn term(&mut self) -> Option<i32> {
+        let mut value = self.factor()?;
+        while let Some(Token::Op(op @ (Operation::Multiply | Operation::Divide))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            value = match op {
+                Operation::Multiply => value.checked_mul(rhs)?,
+                _ if rhs != 0 && value % rhs == 0 => value / rhs,
+                _ => return None,
+            };
+            value = self.checked(value)?;
+        }
+        Some(value)
+    }
+
+    fn factor(&mut self) -> Option<i32> {
+        match self.peek()? {
+            Token::Num(n) => {
+                self.pos += 1;
+                self.checked(n)
+            }
+            Token::Open => {
+                self.pos += 1;
+                let value = self.expr()?;
+                if self.peek() != Some(Token::Close) {
+                    return None;
+                }
+                self.pos += 1;
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Generate an expression of 3 to 5 operands in `min..=max`, sometimes with
+/// one parenthesised sum or difference, whose value and every intermediate
+/// result lie in `0..=max*max`. Returns the expression and its value.
+pub fn generate<R: RandomSource + ?Sized>(rng: &R, (min, max): (u32, u32)) -> (Expression, i32) {
+    let limit = (max * max) as i32;
+    loop {
+        let expression = candidate(rng, min as i32, max as i32);
+        if let Some(value) = expression.eval_within(limit) {
+            return (expression, value);
+        }
+    }
+}
+
+/// One random expression. Divisors are picked to divide what's to their
+/// left where they can be; anything else out of range is left to
+/// `generate` to reject.
+fn candidate<R: RandomSource + ?Sized>(rng: &R, min: i32, max: i32) -> Expression {
+    let operands = rng.range_inclusive(MIN_OPERANDS, MAX_OPERANDS) as usize;
+    // A group of two operands in parentheses one time in three
+    let group = (rng.range(3) == 0).then(|| rng.range(operands as u32 - 1) as usize);
+
+    let mut ops: Vec<Operation> = (1..operands).map(|_| pick_op(rng, Operation::all())).collect();
+    if let Some(at) = group {
+        ops[at] = if rng.range(2) == 0 { Operation::Add } else { Operation::Subtract };
+        // Parentheses only matter next to an x or /
+        let neighbours: Vec<usize> = [at.checked_sub(1), Some(at + 1)]
+            .into_iter()
+            .flatten()
+            .filter(|&i| i < ops.len())
+            .collect();
+        if !neighbours.iter().any(|&i| matches!(ops[i], Operation::Multiply | Operation::Divide)) {
+            let i = neighbours[rng.range(neighbours.len() as u32) as usize];
+            ops[i] = if rng.range(2) == 0 { Operation::Multiply } else { Operation::Divide };
+        }
+    }
+
+    let pick = || rng.range_inclusive(min as u32, max as u32) as i32;
+    let mut tokens = Vec::new();
+    // Value of the x / chain the next operand joins
+    let mut term = 0;
+    let mut i = 0;
+    while i < operands {
+        let op = i.checked_sub(1).map(|j| ops[j]);
+        if let Some(op) = op {
+            tokens.push(Token::Op(op));
+        }
+        let value = if group == Some(i) {
+            let (mut a, mut b) = (pick(), pick());
+            if ops[i] == Operation::Subtract {
+                // Keep the group positive so it can't divide by zero
+                if a < b {
+                    core::mem::swap(&mut a, &mut b);
+                } else if a == b {
+                    ops[i] = Operation::Add;
+                }
+            }
+            tokens.extend([Token::Open, Token::Num(a), Token::Op(ops[i]), Token::Num(b), Token::Close]);
+            let value = ops[i].apply(a, b);
+            i += 2;
+            value
+        } else {
+            let n = match op {
+                Some(Operation::Divide) => {
+                    let divisors: Vec<i32> = (min.max(2)..=max).filter(|d| term % d == 0).collect();
+                    if divisors.is_empty() {
+                        pick()
+                    } else {
+                        divisors[rng.range(divisors.len() as u32) as usize]
+                    }
+                }
+                _ => pick(),
+            };
+            tokens.push(Token::Num(n));
+            i += 1;
+            n
+        };
+        term = match op {
+            Some(Operation::Multiply) => term.saturating_mul(value),
+            Some(Operation::Divide) if value != 0 => term / value,
+            _ => value,
+        };
+    }
+    Expression { tokens }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SeededRng;
+
+    fn parse(text: &str) -> Expression {
+        let tokens = text
+            .split_whitespace()
+            .flat_map(|word| {
+                let mut out = Vec::new();
+                let trimmed = word.trim_start_matches('(');
+                out.extend(core::iter::repeat_n(Token::Open, word.len() - trimmed.len()));
+                let inner = trimmed.trim_end_matches(')');
+                out.push(match inner {
+                    "+" => Token::Op(Operation::Add),
+                    "-" => Token::Op(Operation::Subtract),
+                    "x" => Token::Op(Operation::Multiply),
+                    "/" => Token::Op(Operation::Divide),
+                    n => Token::Num(n.parse().unwrap()),
+                });
+                out.extend(core::iter::repeat_n(Token::Close, trimmed.len() - inner.len()));
+                out
+            })
+            .collect();
+        Expression { tokens }
+    }
+
+    #[test]
+    fn evaluates_with_precedence() {
+        assert_eq!(parse("3 + 4 x 5 - 2").eval(), Some(21));
+        assert_eq!(parse("(3 + 4) x 5").eval(), Some(35));
+        assert_eq!(parse("12 / 3 x 2").eval(), Some(8));
+        assert_eq!(parse("8 - 3 - 2").eval(), Some(3));
+        assert_eq!(parse("20 - (4 + 6) / 5").eval(), Some(18));
+        assert_eq!(parse("7 / 2").eval(), None);
+        assert_eq!(parse("(3 + 4").eval(), None);
+        assert_eq!(parse("2 - 5 + 9").eval_within(100), None);
+        assert_eq!(parse("(3 + 4) x 5").display(), "(3 + 4) x 5");
+    }
+
+    #[test]
+    fn generated_expressions_stay_in_range() {
+        let rng = SeededRng::new(19);
+        let mut grouped = 0;
+        for _ in 0..500 {
+            let (expression, value) = generate(&rng, (2, 19));
+            assert_eq!(expression.eval_within(19 * 19), Some(value));
+            let operands = expression.tokens.iter().filter(|t| matches!(t, Token::Num(_))).count() as u32;
+            assert!((MIN_OPERANDS..=MAX_OPERANDS).contains(&operands));
+            if expression.tokens.contains(&Token::Open) {
+                grouped += 1;
+            }
+        }
+        assert!(grouped > 50, "only {} with parentheses", grouped);
+    }
+}
@@ -0,0 +1,316 @@
+//! Per-fact mastery statistics for Math Drill.
+//!
+//! A fact is one `(Operation, a, b)` pair such as 7 x 8. Every submitted
+//! answer updates that fact's `FactStats`; the heatmap screen lays the
+//! facts for one operation out as a 1–12 x 1–12 table.
+//!
+//! Facts are also scheduled Leitner-style for Practice mode: a fast correct
+//! answer moves a fact up a box, a wrong one sends it back to box 0, and a
+//! fact in box `n` comes due again `INTERVALS[n]` sessions after it was
+//! last seen. Sessions are counted by the history log.
+
+extern crate alloc;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::problems::{generate_mixed, Difficulty, Operation, Problem};
+use crate::rng::RandomSource;
+
+/// How many recent response times are kept per fact for the median.
+const RECENT_TIMES: usize = 9;
+
+/// Answers slower than this count as not yet mastered.
+pub const SLOW_MS: u32 = 4000;
+
+/// Sessions to wait before a fact in each Leitner box is due again.
+const INTERVALS: [u32; 5] = [0, 1, 2, 4, 8];
+const TOP_BOX: u8 = INTERVALS.len() as u8 - 1;
+
+/// Heatmap tables run 1..=GRID_MAX in both directions.
+pub const GRID_MAX: i32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FactKey {
+    pub operation: Operation,
+    pub a: i32,
+    pub b: i32,
+}
+
+impl FactKey {
+    /// The fact behind a problem; `None` unless `problem.is_fact()`.
+    pub fn of(problem: &Problem) -> Option<Self> {
+        let (a, b, _) = problem.arithmetic().filter(|_| problem.is_fact())?;
+        Some(Self { operation: problem.operation, a, b })
+    }
+
+    /// The fact shown at `(row, col)` of an operation's table. Subtraction
+    /// and division are laid out by answer and second operand, so each cell
+    /// is the inverse of the matching addition / multiplication fact.
+    pub fn grid(operation: Operation, row: i32, col: i32) -> Self {
+        let a = match operation {
+            Operation::Subtract => row + col,
+            Operation::Divide => row * col,
+            _ => row,
+        };
+        Self { operation, a, b: col }
+    }
+
+    pub fn problem(&self) -> Problem {
+        Problem::new(self.a, self.b, self.operation, self.operation.apply(self.a, self.b))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FactStats {
+    pub attempts: u32,
+    pub correct: u32,
+    /// Most recent response times, oldest first.
+    pub recent_ms: Vec<u32>,
+    /// Leitner box, 0 (needs work) to `TOP_BOX` (mastered).
+    #[serde(default)]
+    pub box_level: u8,
+    /// Session number the fact was last answered in.
+    #[serde(default)]
+    pub last_session: u32,
+}
+
+impl FactStats {
+    pub fn accuracy_pct(&self) -> u32 {
+        (self.correct * 100).checked_div(self.attempts).unwrap_or(0)
+    }
+
+    pub fn median_ms(&self) -> u32 {
+        if self.recent_ms.is_empty() {
+            return 0;
+        }
+        let mut sorted = self.recent_ms.clone();
+        sorted.sort_unstable();
+        sorted[sorted.len() / 2]
+    }
+
+    pub fn is_due(&self, session: u32) -> bool {
+        session.saturating_sub(self.last_session) >= INTERVALS[self.box_level as usize]
+    }
+}
+
+#[derive(Default)]
+pub struct FactTable {
+    /// Current session number, the clock for the Leitner schedule.
+    pub session: u32,
+    stats: BTreeMap<FactKey, FactStats>,
+    /// Operations with changes not yet written to storage.
+    dirty: BTreeSet<Operation>,
+}
+
+impl FactTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &FactKey) -> Option<&FactStats> {
+        self.stats.get(key)
+    }
+
+    pub fn record(&mut self, problem: &Problem, correct: bool, time_ms: u32) {
+        // Only two-operand facts have a place in the table, and only unsigned ones
+        let key = match (FactKey::of(problem), problem.arithmetic()) {
+            (Some(key), Some((a, b, answer))) if a.min(b).min(answer) >= 0 => key,
+            _ => return,
+        };
+        let entry = self.stats.entry(key).or_default();
+        entry.attempts += 1;
+        if correct {
+            entry.correct += 1;
+        }
+        if entry.recent_ms.len() == RECENT_TIMES {
+            entry.recent_ms.remove(0);
+        }
+        entry.recent_ms.push(time_ms);
+        entry.box_level = if !correct {
+            0
+        } else if time_ms <= SLOW_MS {
+            (entry.box_level + 1).min(TOP_BOX)
+        } else {
+            entry.box_level
+        };
+        entry.last_session = self.session;
+        self.dirty.insert(problem.operation);
+    }
+
+    /// Pick a Practice problem: with `n` facts due, a due fact (weighted
+    /// towards lower boxes) `n` times in `n + 1`, otherwise a fresh random
+    /// one so new facts keep coming in. Never repeats `previous` back to back.
+    pub fn practice_problem<R: RandomSource + ?Sized>(
+        &self,
+        rng: &R,
+        difficulty: Difficulty,
+        previous: Option<&Problem>,
+    ) -> Problem {
+        let previous = previous.and_then(FactKey::of);
+        let due: Vec<(FactKey, u32)> = self
+            .stats
+            .iter()
+            .filter(|(key, stats)| {
+                Some(**key) != previous
+                    && stats.is_due(self.session)
+                    && difficulty.allows(&key.problem())
+            })
+            .map(|(key, stats)| (*key, (TOP_BOX + 1 - stats.box_level) as u32))
+            .collect();
+
+        if rng.range(due.len() as u32 + 1) != 0 {
+            let total: u32 = due.iter().map(|(_, weight)| weight).sum();
+            let mut pick = rng.range(total);
+            for (key, weight) in due.iter() {
+                if pick < *weight {
+                    return key.problem();
+                }
+                pick -= weight;
+            }
+        }
+        generate_mixed(rng, difficulty)
+    }
+
+    /// All facts for one operation, as `(a, b, stats)` for storage.
+    pub fn export(&self, operation: Operation) -> Vec<(i32, i32, FactStats)> {
+        self.stats
+            .iter()
+            .filter(|(key, _)| key.operation == operation)
+            .map(|(key, stats)| (key.a, key.b, stats.clone()))
+            .collect()
+    }
+
+    /// Load stored facts for one operation. If the operation has unsaved
+    /// answers (the PDDB mounted after they were given), they are folded
+    /// into the stored stats instead of being replaced by them.
+    pub fn import(&mut self, operation: Operation, facts: Vec<(i32, i32, FactStats)>) {
+        let unsaved = self.dirty.contains(&operation);
+        for (a, b, stored) in facts {
+            let key = FactKey { operation, a, b };
+            match self.stats.get_mut(&key) {
+                Some(recent) if unsaved => {
+                    recent.attempts += stored.attempts;
+                    recent.correct += stored.correct;
+                    let mut times = stored.recent_ms;
+                    times.append(&mut recent.recent_ms);
+                    let excess = times.len().saturating_sub(RECENT_TIMES);
+                    recent.recent_ms = times.split_off(excess);
+                }
+                _ => {
+                    self.stats.insert(key, stored);
+                }
+            }
+        }
+    }
+
+    pub fn mark_dirty(&mut self, operation: Operation) {
+        self.dirty.insert(operation);
+    }
+
+    /// Operations changed since the last call, clearing the dirty set.
+    pub fn take_dirty(&mut self) -> Vec<Operation> {
+        let ops = self.dirty.iter().copied().collect();
+        self.dirty.clear();
+        ops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SeededRng;
+
+    #[test]
+    fn stats_accumulate_with_bounded_history() {
+        let mut table = FactTable::new();
+        let problem = FactKey::grid(Operation::Multiply, 7, 8).problem();
+        assert_eq!(problem.arithmetic(), Some((7, 8, 56)));
+        for i in 0..12 {
+            table.record(&problem, i % 3 != 0, 1000 + i * 100);
+        }
+        let stats = table.get(&FactKey::of(&problem).unwrap()).unwrap();
+        assert_eq!((stats.attempts, stats.correct), (12, 8));
+        assert_eq!(stats.accuracy_pct(), 66);
+        assert_eq!(stats.recent_ms.len(), RECENT_TIMES);
+        assert_eq!(stats.median_ms(), 1700);
+        assert_eq!(table.take_dirty(), [Operation::Multiply]);
+        assert!(table.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn leitner_boxes_follow_answers() {
+        let mut table = FactTable::new();
+        let problem = FactKey::grid(Operation::Add, 3, 4).problem();
+        let key = FactKey::of(&problem).unwrap();
+        table.record(&problem, true, 1000);
+        table.record(&problem, true, 1000);
+        assert_eq!(table.get(&key).unwrap().box_level, 2);
+        table.record(&problem, true, SLOW_MS + 1);
+        assert_eq!(table.get(&key).unwrap().box_level, 2);
+        assert!(!table.get(&key).unwrap().is_due(1));
+        assert!(table.get(&key).unwrap().is_due(2));
+        table.record(&problem, false, 1000);
+        assert_eq!(table.get(&key).unwrap().box_level, 0);
+        assert!(table.get(&key).unwrap().is_due(0));
+    }
+
+    #[test]
+    fn practice_prefers_due_facts() {
+        let mut table = FactTable::new();
+        let weak = FactKey::grid(Operation::Multiply, 7, 8).problem();
+        let strong = FactKey::grid(Operation::Multiply, 6, 6).problem();
+        table.record(&weak, false, 9000);
+        for _ in 0..4 {
+            table.record(&strong, true, 900);
+        }
+        let rng = SeededRng::new(5);
+        let picks: Vec<Problem> = (0..200)
+            .map(|_| table.practice_problem(&rng, Difficulty::Easy, None))
+            .collect();
+        let weak_picks = picks.iter().filter(|p| FactKey::of(p) == FactKey::of(&weak)).count();
+        assert!(weak_picks > 70, "weak fact picked {} times", weak_picks);
+        assert!(picks.iter().all(|p| Difficulty::Easy.allows(p)));
+
+        // Out of range for Easy, so never scheduled there
+        let hard = FactKey::grid(Operation::Multiply, 12, 12).problem();
+        table.record(&hard, false, 9000);
+        assert!((0..200)
+            .map(|_| table.practice_problem(&rng, Difficulty::Easy, None))
+            .all(|p| FactKey::of(&p) != FactKey::of(&hard)));
+    }
+
+    #[test]
+    fn unsaved_answers_merge_into_imported_stats() {
+        let problem = FactKey::grid(Operation::Add, 3, 4).problem();
+        let key = FactKey::of(&problem).unwrap();
+        let mut stored = FactTable::new();
+        for _ in 0..8 {
+            stored.record(&problem, false, 2000);
+        }
+
+        let mut table = FactTable::new();
+        table.record(&problem, true, 1000);
+        table.record(&problem, true, 1000);
+        table.import(Operation::Add, stored.export(Operation::Add));
+        let stats = table.get(&key).unwrap();
+        assert_eq!((stats.attempts, stats.correct), (10, 2));
+        assert_eq!(stats.recent_ms.len(), RECENT_TIMES);
+        assert_eq!(stats.recent_ms[RECENT_TIMES - 2..], [1000, 1000]);
+        assert_eq!(stats.box_level, 2);
+
+        // Once saved, a reload replaces rather than adds
+        table.take_dirty();
+        table.import(Operation::Add, stored.export(Operation::Add));
+        assert_eq!(table.get(&key).unwrap().attempts, 8);
+    }
+
+    #[test]
+    fn inverse_tables_map_to_exact_facts() {
+        let sub = FactKey::grid(Operation::Subtract, 5, 9).problem();
+        assert_eq!(sub.arithmetic(), Some((14, 9, 5)));
+        let div = FactKey::grid(Operation::Divide, 6, 7).problem();
+        assert_eq!(div.arithmetic(), Some((42, 7, 6)));
+    }
+}
@@ -0,0 +1,251 @@
+//! Exact fractions for the fraction problems.
+//!
+//! Values are kept in lowest terms with a positive denominator, so two
+//! equal fractions always compare equal. Typed answers are a whole number,
+//! `a/b`, or a mixed number `w a/b`, and must be in lowest terms.
+
+extern crate alloc;
+use alloc::format;
+use alloc::string::String;
+
+use serde::{Deserialize, Serialize};
+
+use crate::problems::{Difficulty, Operation};
+use crate::rng::RandomSource;
+
+/// Longest answer the keypad takes on a fraction problem: room for the
+/// longest mixed form a Hard problem can have, `11 107/108`.
+pub const MAX_ANSWER_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fraction {
+    pub num: i32,
+    pub den: i32,
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+impl Fraction {
+    /// `num/den` in lowest terms; `None` if `den` is 0 or it doesn't fit.
+    pub fn new(num: i64, den: i64) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let sign = if den < 0 { -1 } else { 1 };
+        let g = gcd(num, den).max(1);
+        Some(Self {
+            num: i32::try_from(sign * num / g).ok()?,
+            den: i32::try_from(sign * den / g).ok()?,
+        })
+    }
+
+    pub fn whole(n: i32) -> Self {
+        Self { num: n, den: 1 }
+    }
+
+    /// `a op b`, or `None` when dividing by zero or for anything but the four
+    /// arithmetic operations.
+    pub fn apply(op: Operation, a: Self, b: Self) -> Option<Self> {
+        let (an, ad, bn, bd) = (a.num as i64, a.den as i64, b.num as i64, b.den as i64);
+        match op {
+            Operation::Add => Self::new(an * bd + bn * ad, ad * bd),
+            Operation::Subtract => Self::new(an * bd - bn * ad, ad * bd),
+            Operation::Multiply => Self::new(an * bn, ad * bd),
+            Operation::Divide => Self::new(an * bd, ad * bn),
+            _ => None,
+        }
+    }
+
+    /// The whole part and the proper fraction left over, both carrying the
+    /// sign: `-7/4` is `(-1, -3/4)`.
+    pub fn split(&self) -> (i32, Self) {
+        let whole = self.num / self.den;
+        (whole, Self { num: self.num - whole * self.den, den: self.den })
+    }
+
+    /// As typed: `3`, `5/6`, `1 1/4` or `-2 1/3`.
+    pub fn display(&self) -> String {
+        let (whole, rest) = self.split();
+        match (whole, rest.num) {
+            (_, 0) => format!("{}", whole),
+            (0, _) => format!("{}/{}", rest.num, rest.den),
+            _ => format!("{} {}/{}", whole, rest.num.abs(), rest.den),
+        }
+    }
+
+    /// Parse a typed answer. `None` if it's malformed or not in lowest
+    /// terms (`2/4`, `1 4/3`), so only fully simplified answers count.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (sign, text) = match text.trim().strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, text.trim()),
+        };
+        let number = |s: &str| {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            s.parse::<i64>().ok()
+        };
+        let (whole, fraction) = match text.split_once(' ') {
+            Some((whole, fraction)) => (Some(number(whole)?), fraction),
+            None if text.contains('/') => (None, text),
+            None => return Self::new(sign * number(text)?, 1),
+        };
+        let (num, den) = fraction.split_once('/')?;
+        let (num, den) = (number(num)?, number(den)?);
+        if num == 0 || den == 0 || gcd(num, den) != 1 {
+            return None;
+        }
+        match whole {
+            // A mixed number's fraction part has to be proper
+            Some(_) if num >= den => None,
+            Some(whole) => Self::new(sign * (whole * den + num), den),
+            None => Self::new(sign * num, den),
+        }
+    }
+}
+
+/// A fraction problem: `a op b` with an exact fraction answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FractionProblem {
+    pub a: Fraction,
+    pub b: Fraction,
+    pub operation: Operation,
+    pub answer: Fraction,
+}
+
+impl FractionProblem {
+    pub fn question(&self) -> String {
+        format!("{} {} {}", self.a.display(), symbol(self.operation), self.b.display())
+    }
+}
+
+/// Division is shown as ÷ so it can't be mistaken for a fraction bar.
+pub fn symbol(operation: Operation) -> &'static str {
+    match operation {
+        Operation::Divide => "\u{f7}",
+        op => op.symbol(),
+    }
+}
+
+/// Largest denominator and whole part of the operands at each difficulty.
+/// Easy sticks to proper fractions.
+fn limits(difficulty: Difficulty) -> (u32, u32) {
+    match difficulty {
+        Difficulty::Easy => (6, 0),
+        Difficulty::Medium => (10, 2),
+        Difficulty::Hard => (12, 4),
+    }
+}
+
+fn operand<R: RandomSource + ?Sized>(rng: &R, max_den: u32, max_whole: u32) -> Fraction {
+    let den = rng.range_inclusive(2, max_den);
+    let num = rng.range_inclusive(1, den - 1);
+    // Mixed numbers half the time, where the difficulty allows them
+    let whole = if max_whole > 0 && rng.range(2) == 0 {
+        rng.range_inclusive(1, max_whole)
+    } else {
+        0
+    };
+    Fraction::new((whole * den + num) as i64, den as i64).unwrap_or(Fraction::whole(1))
+}
+
+/// Generate `a op b` for proper (and, above Easy, mixed) fractions. A
+/// difference is never negative.
+pub fn generate<R: RandomSource + ?Sized>(rng: &R, operation: Operation, difficulty: Difficulty) -> FractionProblem {
+    let (max_den, max_whole) = limits(difficulty);
+    let mut a = operand(rng, max_den, max_whole);
+    let mut b = operand(rng, max_den, max_whole);
+    if operation == Operation::Subtract && (a.num as i64 * b.den as i64) < (b.num as i64 * a.den as i64) {
+        core::mem::swap(&mut a, &mut b);
+    }
+    // Operands are never zero, so this can't divide by zero
+    let answer = Fraction::apply(operation, a, b).unwrap_or(Fraction::whole(0));
+    FractionProblem { a, b, operation, answer }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SeededRng;
+    use alloc::vec::Vec;
+
+    fn f(num: i64, den: i64) -> Fraction {
+        Fraction::new(num, den).unwrap()
+    }
+
+    #[test]
+    fn arithmetic_is_exact_and_reduced() {
+        assert_eq!(Fraction::apply(Operation::Add, f(1, 2), f(1, 3)), Some(f(5, 6)));
+        assert_eq!(Fraction::apply(Operation::Subtract, f(3, 4), f(1, 4)), Some(f(1, 2)));
+        assert_eq!(Fraction::apply(Operation::Multiply, f(2, 3), f(9, 4)), Some(f(3, 2)));
+        assert_eq!(Fraction::apply(Operation::Divide, f(1, 2), f(1, 4)), Some(Fraction::whole(2)));
+        assert_eq!(Fraction::apply(Operation::Divide, f(1, 2), Fraction::whole(0)), None);
+        assert_eq!(f(6, -8), Fraction { num: -3, den: 4 });
+        assert_eq!(f(7, 4).display(), "1 3/4");
+        assert_eq!(f(-7, 3).display(), "-2 1/3");
+        assert_eq!(f(-1, 3).display(), "-1/3");
+        assert_eq!(f(4, 2).display(), "2");
+    }
+
+    #[test]
+    fn answers_must_be_in_lowest_terms() {
+        assert_eq!(Fraction::parse("7/4"), Some(f(7, 4)));
+        assert_eq!(Fraction::parse("1 3/4"), Some(f(7, 4)));
+        assert_eq!(Fraction::parse("-1 3/4"), Some(f(-7, 4)));
+        assert_eq!(Fraction::parse("3"), Some(Fraction::whole(3)));
+        assert_eq!(Fraction::parse("2/4"), None);
+        assert_eq!(Fraction::parse("1 6/4"), None);
+        assert_eq!(Fraction::parse("1 5/4"), None);
+        assert_eq!(Fraction::parse("4/2"), None);
+        assert_eq!(Fraction::parse("3/0"), None);
+        assert_eq!(Fraction::parse("/4"), None);
+        assert_eq!(Fraction::parse("1/"), None);
+    }
+
+    #[test]
+    fn generated_problems_check_out() {
+        let rng = SeededRng::new(21);
+        for &difficulty in Difficulty::all() {
+            let (max_den, _) = limits(difficulty);
+            for _ in 0..200 {
+                let op = Operation::all()[rng.range(4) as usize];
+                let p = generate(&rng, op, difficulty);
+                assert_eq!(Fraction::apply(op, p.a, p.b), Some(p.answer));
+                assert!(p.a.den as u32 <= max_den && p.b.den as u32 <= max_den);
+                assert!(p.answer.num >= 0);
+                if difficulty == Difficulty::Easy {
+                    assert!(p.a.num < p.a.den && p.b.num < p.b.den);
+                }
+                assert_eq!(Fraction::parse(&p.answer.display()), Some(p.answer));
+            }
+        }
+    }
+
+    #[test]
+    fn every_answer_fits_the_keypad() {
+        for &difficulty in Difficulty::all() {
+            let (max_den, max_whole) = limits(difficulty);
+            let mut operands = Vec::new();
+            for den in 2..=max_den as i64 {
+                for num in 1..den {
+                    for whole in 0..=max_whole as i64 {
+                        operands.push(f(whole * den + num, den));
+                    }
+                }
+            }
+            for &a in &operands {
+                for &b in &operands {
+                    for &op in Operation::all() {
+                        let answer = Fraction::apply(op, a, b).unwrap();
+                        // Subtraction swaps the operands rather than going negative
+                        let shown = answer.display().trim_start_matches('-').len();
+                        assert!(shown <= MAX_ANSWER_LEN, "{:?} {:?} {:?}", a, op, b);
+                    }
+                }
+            }
+        }
+    }
+}
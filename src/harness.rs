@@ -0,0 +1,1174 @@
+//! Headless test driver for the `MathDrillApp` state machine.
+//!
+//! Feeds key sequences and virtual timestamps into `handle_key` / `tick`
+//! on a plain host build. A `SeededRng` stands in for the TRNG, and the
+//! virtual clock fires `tick` at each `next_deadline` the same way the
+//! device's tick thread would.
+
+extern crate alloc;
+use alloc::format;
+
+use crate::app::*;
+use crate::problems::*;
+use crate::rng::SeededRng;
+
+pub struct Driver {
+    pub app: MathDrillApp,
+    pub now_ms: u64,
+    rng: SeededRng,
+}
+
+impl Driver {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            app: MathDrillApp::new(),
+            now_ms: 0,
+            rng: SeededRng::new(seed),
+        }
+    }
+
+    /// Press one key; returns `false` if the app asked to quit.
+    pub fn press(&mut self, key: char) -> bool {
+        self.app.handle_key(key, self.now_ms, &self.rng)
+    }
+
+    /// Press each character of `keys` in order.
+    pub fn keys(&mut self, keys: &str) {
+        for key in keys.chars() {
+            self.press(key);
+        }
+    }
+
+    /// Move the virtual clock forward, ticking at every deadline passed.
+    pub fn advance(&mut self, ms: u64) {
+        let target = self.now_ms + ms;
+        while let Some(at) = self.app.next_deadline() {
+            if at > target {
+                break;
+            }
+            self.now_ms = self.now_ms.max(at);
+            self.app.tick(self.now_ms);
+        }
+        self.now_ms = target;
+    }
+
+    /// From the main menu, move to Start and begin a session.
+    pub fn start(&mut self) {
+        while self.app.menu_field != MenuField::Start {
+            self.press(KEY_DOWN);
+        }
+        self.press(KEY_ENTER);
+    }
+
+    fn current(&self) -> Problem {
+        self.app.current_problem.clone().expect("no current problem")
+    }
+
+    /// Type `value` and submit it.
+    pub fn answer(&mut self, value: i32) {
+        self.keys(&format!("{}", value));
+        self.press(KEY_ENTER);
+    }
+
+    pub fn answer_correctly(&mut self) {
+        let answer = self.current().answer_text();
+        self.keys(&answer);
+        self.press(KEY_ENTER);
+    }
+
+    pub fn answer_wrongly(&mut self) {
+        let problem = self.current();
+        match problem.expected() {
+            Some(expected) => self.answer(expected + 1),
+            None => {
+                self.keys(&format!("{}1", problem.answer_text()));
+                self.press(KEY_ENTER);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perfect_session() {
+        let mut d = Driver::new(1);
+        d.start();
+        assert_eq!(d.app.state, AppState::Playing);
+        for _ in 0..10 {
+            d.advance(2000);
+            d.answer_correctly();
+            assert_eq!(d.app.state, AppState::Feedback);
+            assert!(d.app.feedback_correct);
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.state, AppState::Results);
+        assert_eq!(d.app.correct_count, 10);
+        assert_eq!(d.app.best_streak, 10);
+        assert_eq!(d.app.session_problems.len(), 10);
+        assert_eq!(d.app.avg_time_ms(), 2000);
+    }
+
+    #[test]
+    fn wrong_answer_resets_streak() {
+        let mut d = Driver::new(2);
+        d.start();
+        for i in 0..10 {
+            if i == 3 || i == 7 {
+                d.answer_wrongly();
+                assert!(!d.app.feedback_correct);
+                assert_eq!(d.app.streak, 0);
+            } else {
+                d.answer_correctly();
+            }
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.state, AppState::Results);
+        assert_eq!(d.app.correct_count, 8);
+        assert_eq!(d.app.best_streak, 3);
+        assert_eq!(d.app.streak, 2);
+    }
+
+    #[test]
+    fn feedback_auto_advances_on_tick() {
+        let mut d = Driver::new(3);
+        d.start();
+        d.answer_correctly();
+        assert_eq!(d.app.state, AppState::Feedback);
+        d.advance(1499);
+        assert_eq!(d.app.state, AppState::Feedback);
+        d.advance(1);
+        assert_eq!(d.app.state, AppState::Playing);
+        assert_eq!(d.app.problem_num, 1);
+        assert_eq!(d.app.next_deadline(), None);
+    }
+
+    #[test]
+    fn full_session_without_skipping_feedback() {
+        let mut d = Driver::new(4);
+        d.start();
+        for _ in 0..10 {
+            d.answer_correctly();
+            d.advance(1500);
+        }
+        assert_eq!(d.app.state, AppState::Results);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Menu);
+    }
+
+    #[test]
+    fn answer_editing() {
+        let mut d = Driver::new(5);
+        d.start();
+        d.keys("-12");
+        d.press(KEY_BACKSPACE);
+        assert_eq!(d.app.answer_buffer, "-1");
+        d.keys("-");
+        assert_eq!(d.app.answer_buffer, "-1");
+        d.keys("123456789");
+        assert_eq!(d.app.answer_buffer.len(), 8);
+        for _ in 0..8 {
+            d.press(KEY_BACKSPACE);
+        }
+        assert!(d.app.answer_buffer.is_empty());
+        // Enter on an empty buffer does nothing
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Playing);
+        // A lone sign is a wrong answer, kept as typed
+        d.keys("-");
+        d.press(KEY_ENTER);
+        let attempt = &d.app.session_problems[0];
+        assert!(!attempt.correct);
+        assert_eq!((attempt.user_answer, attempt.given().as_str()), (None, "-"));
+    }
+
+    #[test]
+    fn menu_cycles_options_and_quits() {
+        let mut d = Driver::new(6);
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.op_mode, OpMode::Single(Operation::Subtract));
+        d.press(KEY_DOWN);
+        d.press(KEY_RIGHT);
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.difficulty, Difficulty::Hard);
+        d.press(KEY_UP);
+        d.press(KEY_UP);
+        assert_eq!(d.app.menu_field, MenuField::BestScores);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::BestScores);
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::Menu);
+        assert!(!d.press(KEY_MENU));
+    }
+
+    #[test]
+    fn menu_key_pauses_then_abandons_session() {
+        let mut d = Driver::new(7);
+        d.start();
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::Paused);
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::ConfirmAbandon);
+        // Menu again backs out of the dialog
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::Paused);
+        d.press(KEY_MENU);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Menu);
+
+        // Logged as incomplete, without touching the bests
+        assert_eq!(d.app.storage.history_count(), 1);
+        let record = d.app.storage.load_session(0).unwrap();
+        assert!(record.incomplete);
+        assert_eq!(record.attempts.len(), 1);
+        assert!(d.app.storage.load_best(&Difficulty::Easy, 10).is_none());
+    }
+
+    #[test]
+    fn pause_freezes_every_timer() {
+        let mut d = Driver::new(18);
+        d.app.session_kind = SessionKind::Sprint(30);
+        d.app.problem_limit_secs = [5, 5, 5];
+        d.start();
+        d.advance(2000);
+        d.keys("1");
+        d.press(KEY_MENU);
+        // Nothing can fire while paused, however long it lasts
+        assert_eq!(d.app.next_deadline(), None);
+        d.advance(120_000);
+        assert_eq!(d.app.state, AppState::Paused);
+
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Playing);
+        assert_eq!(d.app.answer_buffer, "1");
+        assert_eq!(d.app.problem_remaining_ms(), Some(3000));
+        assert_eq!(d.app.sprint_remaining_ms(), 28_000);
+        d.press(KEY_BACKSPACE);
+        d.advance(1000);
+        d.answer_correctly();
+        let attempt = d.app.session_problems.last().unwrap();
+        assert_eq!((attempt.time_ms, attempt.first_key_ms), (3000, 2000));
+
+        // Losing focus during feedback pauses too
+        d.app.pause(d.now_ms);
+        d.advance(60_000);
+        assert_eq!(d.app.state, AppState::Paused);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Feedback);
+        d.advance(1500);
+        assert_eq!(d.app.state, AppState::Playing);
+    }
+
+    #[test]
+    fn operation_cycles_both_ways() {
+        let mut d = Driver::new(1);
+        d.press(KEY_LEFT);
+        assert_eq!(d.app.op_mode, OpMode::Practice);
+        d.press(KEY_LEFT);
+        assert_eq!(d.app.op_mode, OpMode::Decimals);
+        d.press(KEY_RIGHT);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.op_mode, OpMode::Single(Operation::Add));
+
+        while d.app.menu_field != MenuField::Mode {
+            d.press(KEY_DOWN);
+        }
+        d.press(KEY_LEFT);
+        assert_eq!(d.app.session_kind, SessionKind::Zen);
+        d.press(KEY_LEFT);
+        d.press(KEY_LEFT);
+        assert_eq!(d.app.session_kind, SessionKind::Sprint(60));
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.session_kind, SessionKind::Survival);
+    }
+
+    #[test]
+    fn same_seed_replays_same_session() {
+        let run = |seed| {
+            let mut d = Driver::new(seed);
+            while d.app.op_mode != OpMode::Mixed {
+                d.press(KEY_LEFT);
+            }
+            d.start();
+            let mut seen = alloc::vec::Vec::new();
+            for _ in 0..10 {
+                seen.push(d.current().display_with_answer());
+                d.answer_correctly();
+                d.press(KEY_ENTER);
+            }
+            seen
+        };
+        assert_eq!(run(8), run(8));
+        assert_ne!(run(8), run(9));
+    }
+
+    #[test]
+    fn best_score_only_improves() {
+        let mut d = Driver::new(10);
+        d.app.refresh_best();
+        assert!(d.app.best_scores[0].is_none());
+        d.start();
+        for i in 0..10 {
+            if i < 6 {
+                d.answer_correctly();
+            } else {
+                d.answer_wrongly();
+            }
+            d.press(KEY_ENTER);
+        }
+        d.app.refresh_best();
+        let best = d.app.best_scores[0].clone().unwrap();
+        assert_eq!((best.correct, best.total, best.streak), (6, 10, 6));
+        // In-memory backend: works, but flags that nothing is persisted
+        assert!(!d.app.save_failed);
+        assert!(!d.app.scores_saved());
+
+        d.press(KEY_ENTER);
+        d.start();
+        for _ in 0..10 {
+            d.answer_wrongly();
+            d.press(KEY_ENTER);
+        }
+        d.app.refresh_best();
+        assert_eq!(d.app.best_scores[0].as_ref().unwrap().correct, 6);
+        assert!(d.app.best_scores[1].is_none());
+    }
+
+    #[test]
+    fn session_length_is_a_persisted_setting() {
+        let mut d = Driver::new(12);
+        for _ in 0..5 {
+            d.press(KEY_DOWN);
+        }
+        assert_eq!(d.app.menu_field, MenuField::Length);
+        d.press(KEY_LEFT);
+        assert_eq!(d.app.session_length, 5);
+        assert_eq!(d.app.storage.load_settings().unwrap().session_length, 5);
+        d.start();
+        for _ in 0..5 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.state, AppState::Results);
+        d.press(KEY_ENTER);
+
+        // Bests are kept per length
+        d.app.refresh_best();
+        assert_eq!(d.app.best_scores[0].as_ref().unwrap().total, 5);
+        d.press(KEY_UP);
+        d.press(KEY_UP);
+        assert_eq!(d.app.menu_field, MenuField::Length);
+        d.press(KEY_RIGHT);
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.session_length, 20);
+        d.app.refresh_best();
+        assert!(d.app.best_scores[0].is_none());
+    }
+
+    #[test]
+    fn sprint_runs_until_the_countdown_expires() {
+        let mut d = Driver::new(13);
+        for _ in 0..5 {
+            d.press(KEY_DOWN);
+        }
+        d.press(KEY_UP);
+        assert_eq!(d.app.menu_field, MenuField::Mode);
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.session_kind, SessionKind::Sprint(60));
+        d.press(KEY_DOWN);
+        d.press(KEY_LEFT);
+        assert_eq!(d.app.session_kind, SessionKind::Sprint(30));
+        assert_eq!(d.app.storage.load_settings().unwrap().session_kind, SessionKind::Sprint(30));
+
+        d.start();
+        // 2s per problem, skipping feedback; the one on screen at 0:00 is dropped
+        while d.app.state != AppState::Results {
+            d.advance(2000);
+            if d.app.state == AppState::Playing {
+                d.answer_correctly();
+                d.press(KEY_ENTER);
+            }
+        }
+        assert_eq!(d.now_ms, 30_000);
+        assert_eq!(d.app.session_problems.len(), 14);
+        assert_eq!(d.app.pace_per_min_x10(), 280);
+
+        d.app.refresh_best();
+        assert_eq!(d.app.best_scores[0].as_ref().unwrap().correct, 14);
+        assert_eq!(d.app.storage.load_session(0).unwrap().kind, SessionKind::Sprint(30));
+        // Sprint bests are kept apart from the fixed-length ones
+        assert!(d.app.storage.load_best(&Difficulty::Easy, 10).is_none());
+    }
+
+    #[test]
+    fn survival_ends_after_three_misses() {
+        let mut d = Driver::new(14);
+        d.app.session_kind = SessionKind::Survival;
+        d.start();
+        for _ in 0..12 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.survival_level(), 2);
+        // Level 3 on Easy: operands up to 18
+        assert!(d.app.session_problems[10..].iter().all(|a| a.problem.arithmetic().unwrap().1 <= 18));
+
+        d.answer_wrongly();
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.lives, 2);
+        // Sitting on a problem past the limit costs a life too
+        d.advance(10_000);
+        assert_eq!(d.app.state, AppState::Feedback);
+        assert!(d.app.session_problems.last().unwrap().timed_out);
+        assert_eq!(d.app.lives, 1);
+        d.press(KEY_ENTER);
+        d.answer_wrongly();
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Results);
+
+        d.app.refresh_best();
+        let best = d.app.best_scores[0].as_ref().unwrap();
+        assert_eq!((best.correct, best.total), (12, 15));
+        assert!(d.app.storage.load_best(&Difficulty::Easy, 10).is_none());
+    }
+
+    #[test]
+    fn problem_limit_times_out_into_feedback() {
+        let mut d = Driver::new(15);
+        while d.app.menu_field != MenuField::TimeLimit {
+            d.press(KEY_DOWN);
+        }
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.problem_limit_secs, [5, 0, 0]);
+        assert_eq!(d.app.storage.load_settings().unwrap().problem_limit_secs, [5, 0, 0]);
+
+        d.start();
+        d.advance(3000);
+        assert_eq!(d.app.problem_remaining_ms(), Some(2000));
+        d.keys("1");
+        d.advance(2000);
+        assert_eq!(d.app.state, AppState::Feedback);
+        assert!(!d.app.feedback_correct);
+        let attempt = d.app.session_problems.last().unwrap();
+        assert!(attempt.timed_out);
+        assert_eq!(attempt.time_ms, 5000);
+
+        // Answering in time is unaffected, and the clock restarts per problem
+        d.advance(1500);
+        assert_eq!(d.app.state, AppState::Playing);
+        d.advance(4000);
+        d.answer_correctly();
+        assert!(d.app.feedback_correct);
+
+        // Other difficulties keep their own (unset) limit
+        d.app.difficulty = Difficulty::Medium;
+        assert_eq!(d.app.problem_limit_ms(), None);
+    }
+
+    #[test]
+    fn zen_retries_misses_and_is_never_scored() {
+        let mut d = Driver::new(16);
+        d.app.session_kind = SessionKind::Zen;
+        d.app.problem_limit_secs = [5, 5, 5];
+        assert_eq!(d.app.problem_limit_ms(), None);
+        d.start();
+        for _ in 0..25 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.state, AppState::Playing);
+
+        let missed = d.current();
+        d.answer_wrongly();
+        d.advance(60_000);
+        assert_eq!(d.app.state, AppState::Playing);
+        assert_eq!(d.current().display(), missed.display());
+        assert!(d.app.answer_buffer.is_empty());
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        assert_ne!(d.current().display(), missed.display());
+
+        // Leaving ends the session: logged, but no best score
+        d.press(KEY_MENU);
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::Results);
+        assert_eq!(d.app.session_problems.len(), 27);
+        assert_eq!(d.app.storage.load_session(0).unwrap().kind, SessionKind::Zen);
+        for secs in SPRINT_SECS {
+            assert!(d.app.storage.load_sprint_best(&Difficulty::Easy, secs).is_none());
+        }
+        assert!(d.app.storage.load_best(&Difficulty::Easy, 10).is_none());
+        assert!(d.app.storage.load_survival_best(&Difficulty::Easy).is_none());
+    }
+
+    #[test]
+    fn retry_round_repeats_until_misses_are_fixed() {
+        let mut d = Driver::new(17);
+        d.start();
+        let mut missed = alloc::vec::Vec::new();
+        for i in 0..10 {
+            if i == 1 || i == 4 {
+                missed.push(d.current().display());
+                d.answer_wrongly();
+            } else {
+                d.answer_correctly();
+            }
+            d.press(KEY_ENTER);
+        }
+        assert!(d.app.can_retry());
+        let key = crate::facts::FactKey::of(&d.app.session_problems[1].problem).unwrap();
+        let stats = |d: &Driver| {
+            let stats = d.app.facts.get(&key).unwrap();
+            (stats.attempts, stats.correct, stats.box_level)
+        };
+        let before = stats(&d);
+        d.press('r');
+        assert_eq!(d.app.retry_round, 1);
+
+        // Miss one again: it comes back alone in a second pass
+        assert!(missed.contains(&d.current().display()));
+        let again = d.current().display();
+        d.answer_wrongly();
+        d.press(KEY_ENTER);
+        assert_ne!(d.current().display(), again);
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.retry_round, 2);
+        assert_eq!(d.current().display(), again);
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+
+        assert_eq!(d.app.state, AppState::Results);
+        assert_eq!(d.app.retry_cleared, Some(2));
+        assert!(!d.app.can_retry());
+        // The session's own score, log and facts are untouched
+        assert_eq!(stats(&d), before);
+        assert_eq!(d.app.correct_count, 8);
+        assert_eq!(d.app.session_problems.len(), 10);
+        assert_eq!(d.app.storage.history_count(), 1);
+    }
+
+    #[test]
+    fn completed_sessions_are_logged() {
+        let mut d = Driver::new(11);
+        d.start();
+        for i in 0..10 {
+            d.advance(1000 + i * 100);
+            if i == 0 {
+                d.answer_wrongly();
+            } else {
+                d.answer_correctly();
+            }
+            d.press(KEY_ENTER);
+        }
+        let seed = d.app.session_rng.seed();
+        d.press(KEY_ENTER);
+        // An abandoned session with nothing answered is not logged
+        d.start();
+        d.press(KEY_MENU);
+        d.press(KEY_MENU);
+        d.press(KEY_ENTER);
+
+        assert_eq!(d.app.storage.history_count(), 1);
+        let record = d.app.storage.load_session(0).unwrap();
+        assert_eq!(record.seed, seed);
+        assert!(!record.incomplete);
+        assert_eq!(record.difficulty, Difficulty::Easy);
+        assert_eq!(record.attempts.len(), 10);
+        assert!(!record.attempts[0].correct);
+        assert_eq!(record.attempts[0].user_answer, Some(record.attempts[0].problem.expected().unwrap() + 1));
+        assert_eq!(record.attempts[9].time_ms, 1900);
+    }
+
+    #[test]
+    fn per_problem_timing() {
+        let mut d = Driver::new(12);
+        d.start();
+        // A backspace with nothing typed isn't the first keystroke
+        d.advance(300);
+        d.press(KEY_BACKSPACE);
+        d.advance(500);
+        d.keys("9");
+        d.advance(400);
+        d.press(KEY_BACKSPACE);
+        d.press(KEY_BACKSPACE);
+        d.advance(300);
+        d.answer_correctly();
+        let attempt = d.app.session_problems[0].clone();
+        assert_eq!(attempt.time_ms, 1500);
+        assert_eq!(attempt.first_key_ms, 800);
+        // The second backspace hit an empty buffer
+        assert_eq!(attempt.backspaces, 1);
+
+        d.press(KEY_ENTER);
+        d.advance(200);
+        d.answer_correctly();
+        let attempt = d.app.session_problems[1].clone();
+        assert_eq!((attempt.time_ms, attempt.first_key_ms, attempt.backspaces), (200, 200, 0));
+    }
+
+    #[test]
+    fn practice_mode_revisits_seen_facts() {
+        let mut d = Driver::new(13);
+        d.press(KEY_RIGHT);
+        d.press(KEY_RIGHT);
+        d.start();
+        let mut first = alloc::vec::Vec::new();
+        for i in 0..10 {
+            first.push(crate::facts::FactKey::of(&d.current()).unwrap());
+            if i == 0 {
+                d.answer_wrongly();
+            } else {
+                d.answer_correctly();
+            }
+            d.press(KEY_ENTER);
+        }
+        d.press(KEY_ENTER);
+
+        while d.app.menu_field != MenuField::Operation {
+            d.press(KEY_UP);
+        }
+        while d.app.op_mode != OpMode::Practice {
+            d.press(KEY_RIGHT);
+        }
+        d.start();
+        // Every fact from the first session is due again, so most
+        // practice problems should be drawn from them
+        let mut revisited = 0;
+        for _ in 0..10 {
+            if crate::facts::FactKey::of(&d.current()).is_some_and(|key| first.contains(&key)) {
+                revisited += 1;
+            }
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert!(revisited >= 5, "only {} facts revisited", revisited);
+        assert_eq!(d.app.state, AppState::Results);
+    }
+
+    #[test]
+    fn quit_mid_session_offers_resume_at_launch() {
+        let mut d = Driver::new(14);
+        d.start();
+        for _ in 0..3 {
+            d.advance(1000);
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        d.advance(700);
+        d.keys("1");
+        let problem = d.current();
+        let rng_state = d.app.session_rng.state();
+        // Quit, as main does on exit
+        d.app.pause(d.now_ms);
+        d.app.save_state();
+
+        // Relaunch with the same storage and a ticktimer restarted from 0
+        let mut next = Driver::new(15);
+        next.app.storage = core::mem::replace(
+            &mut d.app.storage,
+            crate::storage::Storage::new(alloc::boxed::Box::new(crate::storage::MemoryStore::default())),
+        );
+        next.app.load_stored();
+        assert_eq!(next.app.state, AppState::ResumePrompt);
+        next.advance(5000);
+        next.press(KEY_ENTER);
+        assert_eq!(next.app.state, AppState::Playing);
+        assert_eq!(next.current().display_with_answer(), problem.display_with_answer());
+        assert_eq!(next.app.answer_buffer, "1");
+        assert_eq!((next.app.problem_num, next.app.correct_count), (3, 3));
+        assert_eq!(next.app.session_rng.state(), rng_state);
+
+        // Time before the quit still counts toward the problem
+        next.advance(300);
+        next.press(KEY_BACKSPACE);
+        next.answer_correctly();
+        let attempt = next.app.session_problems[3].clone();
+        assert_eq!((attempt.time_ms, attempt.first_key_ms), (1000, 700));
+
+        // Finishing the session drops the saved copy
+        next.press(KEY_ENTER);
+        for _ in 4..10 {
+            next.answer_correctly();
+            next.press(KEY_ENTER);
+        }
+        assert_eq!(next.app.state, AppState::Results);
+        assert!(next.app.storage.load_session_in_progress().is_none());
+    }
+
+    #[test]
+    fn a_resumed_session_keeps_its_settings_and_a_corrupt_one_is_dropped() {
+        let mut d = Driver::new(17);
+        d.app.session_kind = SessionKind::Sprint(30);
+        d.start();
+        d.app.pause(d.now_ms);
+        d.app.save_state();
+
+        // Resuming puts the session's mode in the menu and stores it
+        d.app.session_kind = SessionKind::Standard;
+        d.app.state = AppState::Menu;
+        d.app.load_stored();
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Playing);
+        let settings = d.app.storage.load_settings().unwrap();
+        assert_eq!(settings.session_kind, SessionKind::Sprint(30));
+
+        // A zero-second sprint can't be resumed
+        d.app.pause(d.now_ms);
+        d.app.save_state();
+        let mut saved = d.app.storage.load_session_in_progress().unwrap();
+        saved.session_kind = SessionKind::Sprint(0);
+        d.app.storage.save_session_in_progress(&saved).unwrap();
+        d.app.state = AppState::Menu;
+        d.app.load_stored();
+        assert_eq!(d.app.state, AppState::ResumePrompt);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Menu);
+        assert!(d.app.storage.load_session_in_progress().is_none());
+    }
+
+    #[test]
+    fn discarding_a_saved_session_clears_it() {
+        let mut d = Driver::new(16);
+        d.start();
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        d.app.pause(d.now_ms);
+        d.app.save_state();
+        d.app.state = AppState::Menu;
+        d.app.load_stored();
+        assert_eq!(d.app.state, AppState::ResumePrompt);
+
+        // A stray Menu press only asks
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::ConfirmDiscard);
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::ResumePrompt);
+        assert!(d.app.storage.load_session_in_progress().is_some());
+
+        d.press(KEY_MENU);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Menu);
+        assert!(d.app.storage.load_session_in_progress().is_none());
+        assert_eq!(d.app.storage.history_count(), 1);
+        let record = d.app.storage.load_session(0).unwrap();
+        assert!(record.incomplete);
+        assert_eq!(record.attempts.len(), 1);
+    }
+
+    #[test]
+    fn a_mount_mid_session_leaves_its_settings_alone() {
+        use crate::storage::{MemoryStore, Settings, Storage};
+        let (store, mounted) = MemoryStore::unmounted();
+        mounted.set(true);
+        let mut storage = Storage::new(alloc::boxed::Box::new(store));
+        storage.load_settings();
+        let stored = Settings {
+            session_length: 5,
+            session_kind: SessionKind::Sprint(30),
+            problem_limit_secs: [5, 0, 0],
+        };
+        storage.save_settings(&stored).unwrap();
+        mounted.set(false);
+
+        let mut d = Driver::new(33);
+        d.app.storage = storage;
+        d.app.load_stored();
+        d.start();
+        for _ in 0..3 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        mounted.set(true);
+        d.answer_correctly();
+        d.advance(40_000);
+        assert_eq!(d.app.state, AppState::Playing);
+        assert_eq!(d.app.session_kind, SessionKind::Standard);
+        assert_eq!(d.app.session_length, 10);
+        assert_eq!(d.app.problem_limit_secs, [0, 0, 0]);
+        for _ in 4..10 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.state, AppState::Results);
+        assert!(d.app.storage.load_sprint_best(&Difficulty::Easy, 30).is_none());
+
+        // Back on the menu the stored settings take over
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.session_kind, SessionKind::Sprint(30));
+        assert_eq!((d.app.session_length, d.app.problem_limit_secs), (5, [5, 0, 0]));
+    }
+
+    #[test]
+    fn a_late_pddb_mount_keeps_what_was_stored() {
+        use crate::storage::{MemoryStore, Storage};
+        let attempts = |storage: &mut Storage| {
+            let mut table = crate::facts::FactTable::new();
+            storage.load_facts(&mut table);
+            table.export(Operation::Add).iter().map(|(_, _, stats)| stats.attempts).sum::<u32>()
+        };
+        let (store, mounted) = MemoryStore::unmounted();
+        mounted.set(true);
+
+        // An earlier run: 5-problem sessions, one finished and one quit after two answers
+        let mut d = Driver::new(31);
+        d.app.storage = Storage::new(alloc::boxed::Box::new(store));
+        while d.app.menu_field != MenuField::Length {
+            d.press(KEY_DOWN);
+        }
+        d.press(KEY_LEFT);
+        d.start();
+        for _ in 0..7 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+            if d.app.state == AppState::Results {
+                d.press(KEY_ENTER);
+                d.start();
+            }
+        }
+        d.app.pause(d.now_ms);
+        d.app.save_state();
+        assert_eq!(attempts(&mut d.app.storage), 7);
+
+        // Launch with the PDDB still locked and play a session on the defaults
+        mounted.set(false);
+        let mut next = Driver::new(32);
+        next.app.storage = core::mem::replace(
+            &mut d.app.storage,
+            Storage::new(alloc::boxed::Box::new(MemoryStore::new())),
+        );
+        next.app.load_stored();
+        assert_eq!((next.app.state, next.app.session_length), (AppState::Menu, 10));
+        next.start();
+        for _ in 0..10 {
+            next.answer_correctly();
+            next.press(KEY_ENTER);
+        }
+        assert_eq!(next.app.state, AppState::Results);
+        assert!(next.app.save_failed);
+        next.press(KEY_ENTER);
+
+        // Unlocking brings back the settings and the quit session, and the
+        // facts from both runs are kept
+        mounted.set(true);
+        next.press(KEY_DOWN);
+        assert_eq!(next.app.state, AppState::ResumePrompt);
+        assert_eq!(next.app.session_length, 5);
+        assert!(next.app.storage.load_session_in_progress().is_some());
+        next.app.storage.save_facts(&mut next.app.facts).unwrap();
+        assert_eq!(attempts(&mut next.app.storage), 17);
+    }
+
+    #[test]
+    fn a_settings_save_keeps_an_earlier_failure() {
+        use crate::storage::{MemoryStore, Storage};
+        let (store, mounted) = MemoryStore::unmounted();
+        mounted.set(true);
+        let mut d = Driver::new(34);
+        d.app.storage = Storage::new(alloc::boxed::Box::new(store));
+        d.app.load_stored();
+        d.start();
+        for _ in 0..9 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        d.answer_correctly();
+        // The history write at the end of the session is lost
+        mounted.set(false);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Results);
+        assert!(d.app.save_failed);
+
+        // Changing a setting once it can be saved doesn't hide that
+        mounted.set(true);
+        d.press(KEY_ENTER);
+        while d.app.menu_field != MenuField::Length {
+            d.press(KEY_DOWN);
+        }
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.storage.load_settings().unwrap().session_length, 20);
+        assert!(d.app.save_failed);
+    }
+
+    #[test]
+    fn expression_sessions_skip_the_fact_table() {
+        let mut d = Driver::new(19);
+        while d.app.op_mode != OpMode::Expressions {
+            d.press(KEY_RIGHT);
+        }
+        d.start();
+        for _ in 0..10 {
+            let ProblemKind::Expression { expression, answer } = d.current().kind else {
+                panic!("not an expression");
+            };
+            assert_eq!(expression.eval(), Some(answer));
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.state, AppState::Results);
+        assert_eq!(d.app.correct_count, 10);
+        assert!(Operation::all().iter().all(|&op| d.app.facts.export(op).is_empty()));
+    }
+
+    #[test]
+    fn find_operand_blanks_one_side() {
+        let mut d = Driver::new(20);
+        d.press(KEY_DOWN);
+        d.press(KEY_DOWN);
+        assert_eq!(d.app.menu_field, MenuField::Find);
+        d.press(KEY_RIGHT);
+        assert!(d.app.find_operand);
+        d.start();
+        for i in 0..10 {
+            let problem = d.current();
+            assert_ne!(problem.blank, Blank::Answer);
+            assert!(!problem.display().ends_with("= ?"));
+            if i == 0 {
+                d.answer_wrongly();
+            } else {
+                d.answer_correctly();
+            }
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.correct_count, 9);
+        assert!(!d.app.session_problems[0].correct);
+    }
+
+    #[test]
+    fn fraction_answers_are_typed_with_slash_and_space() {
+        let mut d = Driver::new(21);
+        // No fraction keys on a whole-number problem
+        d.start();
+        d.keys("1/2 3");
+        assert_eq!(d.app.answer_buffer, "123");
+
+        d.press(KEY_MENU);
+        d.press(KEY_MENU);
+        d.press(KEY_ENTER);
+        while d.app.menu_field != MenuField::Operation {
+            d.press(KEY_UP);
+        }
+        while d.app.op_mode != OpMode::Fractions {
+            d.press(KEY_RIGHT);
+        }
+        d.start();
+        // Only one bar, never first, and no space after it
+        d.keys("/1//2 ");
+        assert_eq!(d.app.answer_buffer, "1/2");
+        while !d.app.answer_buffer.is_empty() {
+            d.press(KEY_BACKSPACE);
+        }
+        // Long mixed answers like 4 3/11 x 2 10/11 = 12 52/121 fit
+        d.keys("12 52/121");
+        assert_eq!(d.app.answer_buffer, "12 52/121");
+        while !d.app.answer_buffer.is_empty() {
+            d.press(KEY_BACKSPACE);
+        }
+        let problem = d.current();
+        let ProblemKind::Fraction(fraction) = problem.kind else {
+            panic!("not a fraction");
+        };
+        let answer = fraction.answer;
+        // The same value, but not in lowest terms
+        d.keys(&format!("{}/{}", answer.num * 2, answer.den * 2));
+        d.press(KEY_ENTER);
+        assert!(!d.app.feedback_correct);
+        assert_eq!(d.app.session_problems[0].given(), format!("{}/{}", answer.num * 2, answer.den * 2));
+        d.press(KEY_ENTER);
+        for _ in 1..10 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.correct_count, 9);
+        assert!(Operation::all().iter().all(|&op| d.app.facts.export(op).is_empty()));
+    }
+
+    #[test]
+    fn decimal_answers_allow_trailing_zeros() {
+        let mut d = Driver::new(22);
+        while d.app.op_mode != OpMode::Decimals {
+            d.press(KEY_RIGHT);
+        }
+        d.start();
+        // One point only
+        d.keys("1..2.");
+        assert_eq!(d.app.answer_buffer, "1.2");
+        while !d.app.answer_buffer.is_empty() {
+            d.press(KEY_BACKSPACE);
+        }
+        let answer = d.current().decimal_answer().unwrap();
+        let padded = if answer.places == 0 {
+            format!("{}.00", answer.display())
+        } else {
+            format!("{}0", answer.display())
+        };
+        d.keys(&padded);
+        d.press(KEY_ENTER);
+        assert!(d.app.feedback_correct);
+        assert_eq!(d.app.session_problems[0].given(), padded);
+        d.press(KEY_ENTER);
+        for i in 1..10 {
+            if i == 1 {
+                d.answer_wrongly();
+            } else {
+                d.answer_correctly();
+            }
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.correct_count, 9);
+    }
+
+    #[test]
+    fn percentage_sessions_are_never_facts() {
+        let mut d = Driver::new(23);
+        d.press(KEY_RIGHT);
+        d.press(KEY_DOWN);
+        d.press(KEY_RIGHT);
+        d.press(KEY_RIGHT);
+        d.press(KEY_UP);
+        while d.app.op_mode != OpMode::Single(Operation::Percent) {
+            d.press(KEY_LEFT);
+        }
+        assert_eq!(d.app.difficulty, Difficulty::Hard);
+        // Find-the-operand doesn't apply to percentages, even left on from
+        // another mode
+        d.press(KEY_DOWN);
+        d.press(KEY_DOWN);
+        d.press(KEY_RIGHT);
+        assert!(!d.app.find_operand);
+        d.app.find_operand = true;
+        d.start();
+        for _ in 0..10 {
+            let problem = d.current();
+            assert!(matches!(problem.kind, ProblemKind::Percent(_)));
+            assert_eq!(problem.blank, Blank::Answer);
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.correct_count, 10);
+        assert!(d.app.session_problems.iter().any(|attempt| attempt.given().contains('.')));
+        assert!(Operation::all().iter().all(|&op| d.app.facts.export(op).is_empty()));
+    }
+
+    #[test]
+    fn roots_are_whole_and_never_blanked() {
+        let mut d = Driver::new(24);
+        while d.app.op_mode != OpMode::Single(Operation::SquareRoot) {
+            d.press(KEY_RIGHT);
+        }
+        d.press(KEY_DOWN);
+        d.press(KEY_DOWN);
+        d.press(KEY_RIGHT);
+        assert!(!d.app.find_operand);
+        d.app.find_operand = true;
+        d.start();
+        for _ in 0..10 {
+            let problem = d.current();
+            assert_eq!(problem.blank, Blank::Answer);
+            let (a, _, answer) = problem.arithmetic().unwrap();
+            assert_eq!(answer * answer, a);
+            assert!(problem.display().starts_with('\u{221a}'));
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert_eq!(d.app.correct_count, 10);
+        assert!(Operation::all().iter().all(|&op| d.app.facts.export(op).is_empty()));
+    }
+
+    #[test]
+    fn negatives_sign_operands_and_skip_the_fact_table() {
+        let mut d = Driver::new(25);
+        d.press(KEY_RIGHT);
+        while d.app.menu_field != MenuField::Negatives {
+            d.press(KEY_DOWN);
+        }
+        d.press(KEY_RIGHT);
+        assert!(d.app.negatives);
+        d.start();
+        let mut negative = 0;
+        for _ in 0..10 {
+            let problem = d.current();
+            assert_eq!(problem.operation, Operation::Subtract);
+            let (_, b, answer) = problem.arithmetic().unwrap();
+            if answer < 0 {
+                negative += 1;
+            }
+            if b < 0 {
+                assert!(problem.display().contains(&format!("- ({})", b)));
+            }
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        assert!(negative > 0);
+        assert_eq!(d.app.correct_count, 10);
+        let facts = d.app.facts.export(Operation::Subtract);
+        assert!(facts.iter().all(|&(a, b, _)| a >= b && b >= 0));
+    }
+
+    #[test]
+    fn signed_zeros_never_leave_an_open_blank() {
+        let mut zeros = 0;
+        for seed in 0..40 {
+            let mut d = Driver::new(seed);
+            d.app.op_mode = OpMode::Mixed;
+            d.app.negatives = true;
+            d.app.find_operand = true;
+            d.app.start_session_with_seed(seed, 0);
+            for _ in 0..10 {
+                let problem = d.current();
+                let (a, b, _) = problem.arithmetic().unwrap();
+                if (problem.operation == Operation::Multiply && a * b == 0)
+                    || (problem.operation == Operation::Divide && a == 0)
+                {
+                    zeros += 1;
+                    assert!(problem.has_unique_blank(), "{}", problem.display());
+                }
+                d.answer_correctly();
+                assert!(d.app.feedback_correct);
+                d.press(KEY_ENTER);
+            }
+        }
+        assert!(zeros > 0);
+    }
+
+    #[test]
+    fn negatives_apply_only_where_the_menu_offers_them() {
+        let session = |op_mode, negatives| {
+            let mut d = Driver::new(26);
+            d.app.op_mode = op_mode;
+            d.app.negatives = negatives;
+            d.app.start_session_with_seed(26, 0);
+            let mut problems = alloc::vec::Vec::new();
+            for _ in 0..10 {
+                problems.push(d.current().display_with_answer());
+                d.answer_correctly();
+                d.press(KEY_ENTER);
+            }
+            problems
+        };
+        for op_mode in OP_MODES {
+            let signed = session(op_mode, true);
+            if op_mode.allows_negatives() {
+                assert!(signed.iter().any(|p| p.starts_with('-') || p.contains("(-") || p.contains("= -")));
+            } else {
+                assert_eq!(signed, session(op_mode, false), "{}", op_mode.label());
+            }
+        }
+
+        // Elsewhere the row can't be switched on
+        let mut d = Driver::new(26);
+        while d.app.op_mode != OpMode::Fractions {
+            d.press(KEY_RIGHT);
+        }
+        while d.app.menu_field != MenuField::Negatives {
+            d.press(KEY_DOWN);
+        }
+        d.press(KEY_RIGHT);
+        d.press(KEY_ENTER);
+        assert!(!d.app.negatives);
+    }
+}
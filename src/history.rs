@@ -0,0 +1,75 @@
+//! Session history records for Math Drill.
+//!
+//! Every completed session is appended to the history dictionary (see
+//! `Storage::append_history`) so progress can be tracked over weeks.
+//! Abandoned sessions are appended too, marked incomplete.
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{OpMode, SessionKind};
+use crate::problems::{Difficulty, Problem};
+
+/// One answered (or timed-out) problem.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attempt {
+    pub problem: Problem,
+    /// The integer answer given; `None` when it timed out, wasn't a whole
+    /// number (a lone `-`), or the problem doesn't take one.
+    pub user_answer: Option<i32>,
+    pub correct: bool,
+    /// Time from the problem appearing to the answer being submitted.
+    pub time_ms: u32,
+    /// Time from the problem appearing to the first keystroke.
+    #[serde(default)]
+    pub first_key_ms: u32,
+    /// Backspaces pressed while answering.
+    #[serde(default)]
+    pub backspaces: u32,
+    /// The time limit ran out before an answer was submitted.
+    #[serde(default)]
+    pub timed_out: bool,
+    /// The answer as typed whenever `user_answer` can't hold it, e.g. for
+    /// a fraction or decimal problem; empty otherwise.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_input: String,
+}
+
+impl Attempt {
+    /// The answer given, as typed.
+    pub fn given(&self) -> String {
+        match self.user_answer {
+            Some(answer) if self.user_input.is_empty() => alloc::format!("{}", answer),
+            _ => self.user_input.clone(),
+        }
+    }
+}
+
+/// A finished or abandoned session as stored in history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// Wall-clock time the session finished, seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub op_mode: OpMode,
+    pub difficulty: Difficulty,
+    /// Seed the session's problems were generated from.
+    pub seed: u64,
+    /// How the session was bounded; older sessions were all Standard.
+    #[serde(default)]
+    pub kind: SessionKind,
+    /// Abandoned before the end; never counted toward best scores.
+    #[serde(default)]
+    pub incomplete: bool,
+    pub attempts: Vec<Attempt>,
+}
+
+/// Current wall-clock time, or 0 if the clock isn't available.
+pub fn unix_time_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -6,33 +6,74 @@
 #![cfg_attr(target_os = "none", no_std)]
 #![cfg_attr(target_os = "none", no_main)]
 
-
+// Host builds only compile the state machine for the test suite
+#[cfg(any(target_os = "xous", test))]
 mod app;
+#[cfg(any(target_os = "xous", test))]
+mod decimal;
+#[cfg(any(target_os = "xous", test))]
+mod expression;
+#[cfg(any(target_os = "xous", test))]
+mod facts;
+#[cfg(any(target_os = "xous", test))]
+mod fraction;
+#[cfg(any(target_os = "xous", test))]
+mod history;
+#[cfg(any(target_os = "xous", test))]
+mod percent;
+#[cfg(any(target_os = "xous", test))]
 mod problems;
+#[cfg(any(target_os = "xous", test))]
 mod rng;
+#[cfg(any(target_os = "xous", test))]
 mod storage;
+#[cfg(target_os = "xous")]
+mod timer;
+#[cfg(any(target_os = "xous", test))]
+mod render;
+#[cfg(any(target_os = "xous", test))]
 mod ui;
 
+#[cfg(test)]
+mod harness;
+
+#[cfg(target_os = "xous")]
 use app::MathDrillApp;
+#[cfg(target_os = "xous")]
+use render::GamRenderer;
+#[cfg(target_os = "xous")]
 use rng::Rng;
+#[cfg(target_os = "xous")]
+use timer::Timer;
+#[cfg(target_os = "xous")]
 use num_traits::FromPrimitive;
+#[cfg(target_os = "xous")]
 use num_traits::ToPrimitive;
 
+#[cfg(target_os = "xous")]
 const SERVER_NAME: &str = "_Math Drill_";
+#[cfg(target_os = "xous")]
 const APP_NAME: &str = "Math Drill";
 
+#[cfg(target_os = "xous")]
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 enum AppOp {
     Redraw = 0,
     Rawkeys = 1,
     FocusChange = 2,
+    Tick = 3,
     Quit = 255,
 }
 
+/// The app itself only runs on Xous; host builds exist for `cargo test`.
+#[cfg(not(target_os = "xous"))]
+fn main() {}
+
+#[cfg(target_os = "xous")]
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
-    log::info!("Math Drill starting, PID {}", xous::process::id());
+    log::info!("{} starting, PID {}", APP_NAME, xous::process::id());
 
     let xns = xous_names::XousNames::new().unwrap();
     let sid = xns
@@ -41,6 +82,7 @@ fn main() -> ! {
     let gam = gam::Gam::new(&xns).expect("can't connect to GAM");
     let tt = ticktimer_server::Ticktimer::new().unwrap();
     let rng = Rng::new(&xns);
+    let timer = Timer::spawn(sid, AppOp::Tick.to_usize().unwrap());
 
     let token = gam
         .register_ux(gam::UxRegistration {
@@ -65,15 +107,11 @@ fn main() -> ! {
         .expect("couldn't get dimensions");
     log::info!("Canvas size: {:?}", screensize);
 
+    let mut renderer = GamRenderer::new(&gam, content);
     let mut app = MathDrillApp::new();
     app.init_storage();
     let mut allow_redraw = true;
-    ui::draw(&app, &gam, content);
-
-    // Cache best scores for display
-    let mut cached_easy = app.get_best(&problems::Difficulty::Easy);
-    let mut cached_medium = app.get_best(&problems::Difficulty::Medium);
-    let mut cached_hard = app.get_best(&problems::Difficulty::Hard);
+    ui::draw(&app, &mut renderer);
 
     loop {
         let msg = xous::receive_message(sid).unwrap();
@@ -83,16 +121,7 @@ fn main() -> ! {
             Some(AppOp::Redraw) => {
                 if allow_redraw {
                     app.needs_redraw = true;
-                    if app.state == app::AppState::BestScores {
-                        ui::draw_best_with_data(
-                            &gam, content,
-                            cached_easy.as_ref(),
-                            cached_medium.as_ref(),
-                            cached_hard.as_ref(),
-                        );
-                    } else {
-                        ui::draw(&app, &gam, content);
-                    }
+                    ui::draw(&app, &mut renderer);
                 }
             }
             Some(AppOp::Rawkeys) => xous::msg_scalar_unpack!(msg, k1, k2, k3, k4, {
@@ -104,57 +133,48 @@ fn main() -> ! {
                 ];
                 let mut should_quit = false;
                 for &key in keys.iter() {
-                    if key != '\u{0000}' {
-                        if !app.handle_key(key, now_ms, &rng) {
-                            should_quit = true;
-                            break;
-                        }
+                    if key != '\u{0000}' && !app.handle_key(key, now_ms, &rng) {
+                        should_quit = true;
+                        break;
                     }
                 }
                 if should_quit { break; }
 
-                // Check feedback timeout
-                app.check_feedback_timeout(now_ms, &rng);
-
                 if app.needs_redraw && allow_redraw {
-                    if app.state == app::AppState::BestScores {
-                        // Refresh cache
-                        cached_easy = app.get_best(&problems::Difficulty::Easy);
-                        cached_medium = app.get_best(&problems::Difficulty::Medium);
-                        cached_hard = app.get_best(&problems::Difficulty::Hard);
-                        ui::draw_best_with_data(
-                            &gam, content,
-                            cached_easy.as_ref(),
-                            cached_medium.as_ref(),
-                            cached_hard.as_ref(),
-                        );
-                    } else {
-                        ui::draw(&app, &gam, content);
-                    }
+                    ui::draw(&app, &mut renderer);
                     app.needs_redraw = false;
                 }
             }),
+            Some(AppOp::Tick) => {
+                app.tick(now_ms);
+                if app.needs_redraw && allow_redraw {
+                    ui::draw(&app, &mut renderer);
+                    app.needs_redraw = false;
+                }
+            }
             Some(AppOp::FocusChange) => xous::msg_scalar_unpack!(msg, state_code, _, _, _, {
                 match gam::FocusState::convert_focus_change(state_code) {
                     gam::FocusState::Background => {
                         allow_redraw = false;
+                        app.pause(now_ms);
                         app.save_state();
                     }
                     gam::FocusState::Foreground => {
                         allow_redraw = true;
-                        // Refresh best scores cache
-                        cached_easy = app.get_best(&problems::Difficulty::Easy);
-                        cached_medium = app.get_best(&problems::Difficulty::Medium);
-                        cached_hard = app.get_best(&problems::Difficulty::Hard);
-                        ui::draw(&app, &gam, content);
+                        app.refresh_best();
+                        ui::draw(&app, &mut renderer);
                     }
                 }
             }),
             Some(AppOp::Quit) => break,
             _ => log::warn!("unknown opcode: {:?}", msg.body.id()),
         }
+
+        // No ticks while backgrounded; the next FocusChange re-arms the timer
+        timer.set_deadline(if allow_redraw { app.next_deadline() } else { None });
     }
 
+    app.pause(tt.elapsed_ms());
     app.save_state();
     xns.unregister_server(sid).unwrap();
     xous::destroy_server(sid).unwrap();
@@ -0,0 +1,138 @@
+//! Percentage problems: `15% of 80`, `12 is what % of 48` and
+//! `80 increased by 25%`.
+//!
+//! Operands are whole numbers; answers are exact `Decimal`s that end within
+//! a number of places set by the operand range, so the easiest problems
+//! come out whole.
+
+extern crate alloc;
+use alloc::format;
+use alloc::string::String;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decimal::Decimal;
+use crate::problems::Operation;
+use crate::rng::RandomSource;
+
+/// How a percentage problem is asked.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Phrasing {
+    /// `a% of b`.
+    Of,
+    /// `a is what % of b`.
+    WhatPercent,
+    /// `a increased by b%`.
+    IncreasedBy,
+}
+
+/// A percentage problem with whole-number operands.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PercentProblem {
+    pub phrasing: Phrasing,
+    pub a: i32,
+    pub b: i32,
+    pub answer: Decimal,
+}
+
+impl PercentProblem {
+    pub fn question(&self) -> String {
+        match self.phrasing {
+            Phrasing::Of => format!("{}% of {}", self.a, self.b),
+            Phrasing::WhatPercent => format!("{} is what % of {}", self.a, self.b),
+            Phrasing::IncreasedBy => format!("{} increased by {}%", self.a, self.b),
+        }
+    }
+}
+
+/// Percentages and answer places allowed for an operand range, by its top:
+/// the Easy, Medium and Hard ranges top out at 9, 19 and 49.
+struct Limits {
+    percents: &'static [i32],
+    /// Most decimal places in an answer.
+    places: u32,
+}
+
+const EASY_PERCENTS: &[i32] = &[10, 20, 25, 50, 75];
+const MEDIUM_PERCENTS: &[i32] = &[5, 10, 15, 20, 25, 30, 40, 50, 60, 70, 75, 80, 90];
+const HARD_PERCENTS: &[i32] = &[
+    1, 2, 4, 5, 8, 10, 12, 15, 16, 20, 24, 25, 30, 35, 40, 45, 48, 50, 55, 60, 64, 65, 70, 75, 80,
+    85, 90, 95, 120, 125, 150, 200,
+];
+
+fn limits(max: u32) -> Limits {
+    match max {
+        0..=9 => Limits { percents: EASY_PERCENTS, places: 0 },
+        10..=19 => Limits { percents: MEDIUM_PERCENTS, places: 1 },
+        _ => Limits { percents: HARD_PERCENTS, places: 2 },
+    }
+}
+
+/// `a op b` for the answer: the part, the percentage, or the new amount.
+fn answer(phrasing: Phrasing, a: i32, b: i32) -> Option<Decimal> {
+    let whole = |n: i32| Decimal::new(n as i64, 0);
+    match phrasing {
+        Phrasing::Of => Decimal::apply(Operation::Percent, whole(a), whole(b)),
+        Phrasing::WhatPercent => Decimal::apply(Operation::Divide, whole(a * 100), whole(b)),
+        Phrasing::IncreasedBy => Decimal::apply(Operation::Percent, whole(b + 100), whole(a)),
+    }
+}
+
+/// Generate a percentage problem on a base from `10 * min` to `10 * max + 9`.
+/// Draws are retried until the answer ends within the range's places.
+pub fn generate<R: RandomSource + ?Sized>(rng: &R, (min, max): (u32, u32)) -> PercentProblem {
+    let limits = limits(max);
+    let phrasing = [Phrasing::Of, Phrasing::WhatPercent, Phrasing::IncreasedBy][rng.range(3) as usize];
+    loop {
+        let percent = limits.percents[rng.range(limits.percents.len() as u32) as usize];
+        let base = rng.range_inclusive(min * 10, max * 10 + 9) as i32;
+        let (a, b) = match phrasing {
+            Phrasing::Of => (percent, base),
+            // The part is shown, so it has to be whole
+            Phrasing::WhatPercent if percent * base % 100 != 0 => continue,
+            Phrasing::WhatPercent => (percent * base / 100, base),
+            Phrasing::IncreasedBy => (base, percent),
+        };
+        if let Some(answer) = answer(phrasing, a, b).filter(|answer| answer.places <= limits.places) {
+            return PercentProblem { phrasing, a, b, answer };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SeededRng;
+
+    fn problem(phrasing: Phrasing, a: i32, b: i32) -> PercentProblem {
+        PercentProblem { phrasing, a, b, answer: answer(phrasing, a, b).unwrap() }
+    }
+
+    #[test]
+    fn phrasings_read_and_answer_correctly() {
+        let p = problem(Phrasing::Of, 15, 80);
+        assert_eq!(p.question(), "15% of 80");
+        assert_eq!(p.answer.display(), "12");
+        let p = problem(Phrasing::WhatPercent, 12, 48);
+        assert_eq!(p.question(), "12 is what % of 48");
+        assert_eq!(p.answer.display(), "25");
+        let p = problem(Phrasing::IncreasedBy, 80, 25);
+        assert_eq!(p.question(), "80 increased by 25%");
+        assert_eq!(p.answer.display(), "100");
+        assert_eq!(problem(Phrasing::Of, 15, 33).answer.display(), "4.95");
+        assert_eq!(problem(Phrasing::WhatPercent, 1, 8).answer.display(), "12.5");
+    }
+
+    #[test]
+    fn answers_end_within_the_range_places() {
+        let rng = SeededRng::new(23);
+        for (range, places) in [((1, 9), 0), ((2, 19), 1), ((2, 49), 2)] {
+            for _ in 0..300 {
+                let p = generate(&rng, range);
+                assert!(p.answer.places <= places, "{} = {}", p.question(), p.answer.display());
+                assert_eq!(answer(p.phrasing, p.a, p.b), Some(p.answer));
+                assert!(p.answer.display().len() <= 8);
+            }
+        }
+    }
+}
@@ -1,34 +1,102 @@
 //! Problem generation and answer checking for Math Drill.
 //!
-//! Uses TRNG for cryptographically random operand generation.
-//! Division always produces clean integer results.
+//! Operands are drawn from any `RandomSource` — the TRNG on device, or a
+//! seeded generator for replayable sessions and tests.
+//! Division always produces clean integer results, and unless operands are
+//! signed (`generate_signed`) subtraction never goes negative. Multi-operand
+//! expressions live in `expression`, fractions in `fraction`, decimals in
+//! `decimal` and percentages in `percent`.
 
 extern crate alloc;
 use alloc::string::String;
 use alloc::format;
 
-use crate::rng::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::decimal::{self, Decimal, DecimalProblem};
+use crate::expression::{self, Expression};
+use crate::fraction::{self, Fraction, FractionProblem};
+use crate::percent::{self, PercentProblem};
+use crate::rng::RandomSource;
 
 /// Arithmetic operation type.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Operation {
     Add,
     Subtract,
     Multiply,
     Divide,
+    /// `a% of b`, asked in several phrasings; never a fact.
+    Percent,
+    /// `a²`; like the other powers and roots, `b` holds the exponent.
+    Square,
+    /// `a³`.
+    Cube,
+    /// `a^b`.
+    Power,
+    /// `√a`, a perfect square; `b` is 2.
+    SquareRoot,
+    /// `³√a`, a perfect cube; `b` is 3.
+    CubeRoot,
 }
 
 impl Operation {
+    /// The four arithmetic operations, which Mixed, expressions, fractions
+    /// and decimals draw from.
     pub fn all() -> &'static [Operation] {
         &[Operation::Add, Operation::Subtract, Operation::Multiply, Operation::Divide]
     }
 
+    /// One of the four in `all`, the only ones with fact tables.
+    pub fn is_arithmetic(&self) -> bool {
+        Operation::all().contains(self)
+    }
+
     pub fn symbol(&self) -> &'static str {
         match self {
             Operation::Add => "+",
             Operation::Subtract => "-",
             Operation::Multiply => "x",
             Operation::Divide => "/",
+            Operation::Percent => "%",
+            Operation::Square => "\u{b2}",
+            Operation::Cube => "\u{b3}",
+            Operation::Power => "^",
+            Operation::SquareRoot => "\u{221a}",
+            Operation::CubeRoot => "\u{b3}\u{221a}",
+        }
+    }
+
+    /// Short stable name used in storage keys.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Operation::Add => "add",
+            Operation::Subtract => "sub",
+            Operation::Multiply => "mul",
+            Operation::Divide => "div",
+            Operation::Percent => "pct",
+            Operation::Square => "sq",
+            Operation::Cube => "cube",
+            Operation::Power => "pow",
+            Operation::SquareRoot => "sqrt",
+            Operation::CubeRoot => "cbrt",
+        }
+    }
+
+    /// Apply the operation; division and roots truncate, callers keep them
+    /// exact. Percentages aren't integers (`percent` works them out) and
+    /// give 0.
+    pub fn apply(&self, a: i32, b: i32) -> i32 {
+        match self {
+            Operation::Add => a + b,
+            Operation::Subtract => a - b,
+            Operation::Multiply => a * b,
+            Operation::Divide => a.checked_div(b).unwrap_or(0),
+            Operation::Square | Operation::Cube | Operation::Power => {
+                a.checked_pow(b as u32).unwrap_or(0)
+            }
+            Operation::SquareRoot | Operation::CubeRoot => root(a.max(0) as u32, b as u32) as i32,
+            _ => 0,
         }
     }
 
@@ -38,12 +106,23 @@ impl Operation {
             Operation::Subtract => "Subtraction",
             Operation::Multiply => "Multiplication",
             Operation::Divide => "Division",
+            Operation::Percent => "Percentage",
+            Operation::Square => "Squares",
+            Operation::Cube => "Cubes",
+            Operation::Power => "Powers",
+            Operation::SquareRoot => "Square Roots",
+            Operation::CubeRoot => "Cube Roots",
         }
     }
 }
 
+/// The largest `r` with `r^index <= n`.
+fn root(n: u32, index: u32) -> u32 {
+    (1..=n).take_while(|r| r.checked_pow(index).is_some_and(|p| p <= n)).last().unwrap_or(0)
+}
+
 /// Difficulty level controlling operand ranges.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Difficulty {
     Easy,
     Medium,
@@ -55,6 +134,14 @@ impl Difficulty {
         &[Difficulty::Easy, Difficulty::Medium, Difficulty::Hard]
     }
 
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
     pub fn label(&self) -> &'static str {
         match self {
             Difficulty::Easy => "Easy (1-9)",
@@ -78,40 +165,291 @@ impl Difficulty {
             Difficulty::Hard => (2, 49),
         }
     }
+
+    /// Operand range after `level` steps of Survival escalation: the top
+    /// grows by half its base value per level, capped at 999.
+    pub fn operand_range_at(&self, level: u32) -> (u32, u32) {
+        let (min, max) = self.operand_range();
+        (min, (max + max * level / 2).min(999))
+    }
+
+    /// Whether `generate` could have produced `problem` at this difficulty.
+    pub fn allows(&self, problem: &Problem) -> bool {
+        let (min, max) = self.operand_range();
+        let in_range = |v: i32| v >= min as i32 && v <= max as i32;
+        let Some((a, b, answer)) = problem.arithmetic() else {
+            return false;
+        };
+        match problem.operation {
+            Operation::Add | Operation::Subtract | Operation::Multiply => in_range(a) && in_range(b),
+            Operation::Divide => in_range(b) && in_range(answer),
+            _ => false,
+        }
+    }
+}
+
+/// Which part of a problem the user has to find.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Blank {
+    /// The result: `7 x 8 = ?`.
+    #[default]
+    Answer,
+    /// The first operand: `? x 8 = 56`.
+    Left,
+    /// The second operand: `7 x ? = 56`.
+    Right,
 }
 
-/// A math problem with two operands and an operation.
+/// What a problem asks, with its operands and answer.
 #[derive(Debug, Clone)]
+pub enum ProblemKind {
+    /// Two integer operands; for powers and roots `b` is the exponent.
+    Arithmetic { a: i32, b: i32, answer: i32 },
+    /// Three to five operands with mixed operators, e.g. `3 + 4 x 5 - 2`.
+    Expression { expression: Expression, answer: i32 },
+    Fraction(FractionProblem),
+    Decimal(DecimalProblem),
+    Percent(PercentProblem),
+}
+
+/// A math problem: its operation (an expression's first operator), what
+/// it asks, and which part the user has to find.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredProblem", into = "StoredProblem")]
 pub struct Problem {
-    pub a: i32,
-    pub b: i32,
     pub operation: Operation,
-    pub answer: i32,
+    pub kind: ProblemKind,
+    /// The unknown; always `Answer` unless `is_fact`.
+    pub blank: Blank,
+}
+
+/// `Problem` as stored in history and saved sessions: two operands and an
+/// answer, which other kinds leave as 0 beside the one field they set.
+#[derive(Serialize, Deserialize)]
+struct StoredProblem {
+    a: i32,
+    b: i32,
+    operation: Operation,
+    answer: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expression: Option<Expression>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fraction: Option<FractionProblem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    decimal: Option<DecimalProblem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    percent: Option<PercentProblem>,
+    #[serde(default)]
+    blank: Blank,
+}
+
+impl From<StoredProblem> for Problem {
+    fn from(stored: StoredProblem) -> Self {
+        let StoredProblem { a, b, operation, answer, expression, fraction, decimal, percent, blank } = stored;
+        let kind = if let Some(expression) = expression {
+            ProblemKind::Expression { expression, answer }
+        } else if let Some(fraction) = fraction {
+            ProblemKind::Fraction(fraction)
+        } else if let Some(decimal) = decimal {
+            ProblemKind::Decimal(decimal)
+        } else if let Some(percent) = percent {
+            ProblemKind::Percent(percent)
+        } else {
+            ProblemKind::Arithmetic { a, b, answer }
+        };
+        Self { operation, kind, blank }
+    }
+}
+
+impl From<Problem> for StoredProblem {
+    fn from(problem: Problem) -> Self {
+        let mut stored = StoredProblem {
+            a: 0,
+            b: 0,
+            operation: problem.operation,
+            answer: 0,
+            expression: None,
+            fraction: None,
+            decimal: None,
+            percent: None,
+            blank: problem.blank,
+        };
+        match problem.kind {
+            ProblemKind::Arithmetic { a, b, answer } => {
+                (stored.a, stored.b, stored.answer) = (a, b, answer);
+            }
+            ProblemKind::Expression { expression, answer } => {
+                stored.expression = Some(expression);
+                stored.answer = answer;
+            }
+            ProblemKind::Fraction(fraction) => stored.fraction = Some(fraction),
+            ProblemKind::Decimal(decimal) => stored.decimal = Some(decimal),
+            ProblemKind::Percent(percent) => stored.percent = Some(percent),
+        }
+        stored
+    }
 }
 
 impl Problem {
+    /// A plain two-operand problem asking for the result.
+    pub fn new(a: i32, b: i32, operation: Operation, answer: i32) -> Self {
+        Self::of_kind(operation, ProblemKind::Arithmetic { a, b, answer })
+    }
+
+    fn of_kind(operation: Operation, kind: ProblemKind) -> Self {
+        Self { operation, kind, blank: Blank::Answer }
+    }
+
+    /// Operands and answer, `(a, b, answer)`, of a two-operand problem.
+    pub fn arithmetic(&self) -> Option<(i32, i32, i32)> {
+        match self.kind {
+            ProblemKind::Arithmetic { a, b, answer } => Some((a, b, answer)),
+            _ => None,
+        }
+    }
+
+    /// A plain two-operand integer problem, as kept in the fact table.
+    pub fn is_fact(&self) -> bool {
+        self.operation.is_arithmetic() && self.arithmetic().is_some()
+    }
+
+    /// Whether only one number fits the blank. A zero factor or dividend
+    /// leaves it open: anything makes `0 x ? = 0` (and `0 / ? = 0`) true.
+    pub fn has_unique_blank(&self) -> bool {
+        let Some((a, b, _)) = self.arithmetic() else {
+            return true;
+        };
+        match (self.operation, self.blank) {
+            (Operation::Multiply, Blank::Left) => b != 0,
+            (Operation::Multiply | Operation::Divide, Blank::Right) => a != 0,
+            _ => true,
+        }
+    }
+
+    /// False for fraction, decimal and percentage problems, whose answers
+    /// are typed with `/` or `.`.
+    pub fn has_integer_answer(&self) -> bool {
+        self.expected().is_some()
+    }
+
+    /// The answer to a decimal or percentage problem.
+    pub fn decimal_answer(&self) -> Option<Decimal> {
+        match &self.kind {
+            ProblemKind::Decimal(decimal) => Some(decimal.answer),
+            ProblemKind::Percent(percent) => Some(percent.answer),
+            _ => None,
+        }
+    }
+
+    /// The question without its answer, e.g. `7 x 8` or `3 + 4 x 5 - 2`.
+    pub fn question(&self) -> String {
+        match &self.kind {
+            ProblemKind::Arithmetic { a, b, .. } => {
+                let symbol = self.operation.symbol();
+                match self.operation {
+                    Operation::Square | Operation::Cube => format!("{}{}", a, symbol),
+                    Operation::Power => format!("{}{}{}", a, symbol, b),
+                    Operation::SquareRoot | Operation::CubeRoot => format!("{}{}", symbol, a),
+                    _ => format!("{} {} {}", a, symbol, right_operand(*b)),
+                }
+            }
+            ProblemKind::Expression { expression, .. } => expression.display(),
+            ProblemKind::Fraction(fraction) => fraction.question(),
+            ProblemKind::Decimal(decimal) => decimal.question(),
+            ProblemKind::Percent(percent) => percent.question(),
+        }
+    }
+
     pub fn display(&self) -> String {
-        format!("{} {} {} = ?", self.a, self.operation.symbol(), self.b)
+        let op = self.operation.symbol();
+        match (self.blank, &self.kind) {
+            (Blank::Left, ProblemKind::Arithmetic { b, answer, .. }) => {
+                format!("? {} {} = {}", op, right_operand(*b), answer)
+            }
+            (Blank::Right, ProblemKind::Arithmetic { a, answer, .. }) => {
+                format!("{} {} ? = {}", a, op, answer)
+            }
+            _ => format!("{} = ?", self.question()),
+        }
     }
 
+    /// The whole equation, blank filled in.
     pub fn display_with_answer(&self) -> String {
-        format!("{} {} {} = {}", self.a, self.operation.symbol(), self.b, self.answer)
+        let answer = match &self.kind {
+            ProblemKind::Arithmetic { answer, .. } | ProblemKind::Expression { answer, .. } => {
+                format!("{}", answer)
+            }
+            ProblemKind::Fraction(fraction) => fraction.answer.display(),
+            ProblemKind::Decimal(decimal) => decimal.answer.display(),
+            ProblemKind::Percent(percent) => percent.answer.display(),
+        };
+        format!("{} = {}", self.question(), answer)
+    }
+
+    /// What the user has to type to be right.
+    #[cfg(test)]
+    pub fn answer_text(&self) -> String {
+        match (&self.kind, self.expected()) {
+            (ProblemKind::Fraction(fraction), _) => fraction.answer.display(),
+            (_, Some(expected)) => format!("{}", expected),
+            _ => self.decimal_answer().map(|answer| answer.display()).unwrap_or_default(),
+        }
+    }
+
+    /// The number the user has to type: the answer or the blanked operand.
+    /// `None` for problems without an integer answer.
+    pub fn expected(&self) -> Option<i32> {
+        match (&self.kind, self.blank) {
+            (ProblemKind::Arithmetic { a, .. }, Blank::Left) => Some(*a),
+            (ProblemKind::Arithmetic { b, .. }, Blank::Right) => Some(*b),
+            (ProblemKind::Arithmetic { answer, .. }, _) => Some(*answer),
+            (ProblemKind::Expression { answer, .. }, _) => Some(*answer),
+            _ => None,
+        }
     }
 
     pub fn check(&self, user_answer: i32) -> bool {
-        user_answer == self.answer
+        self.expected() == Some(user_answer)
+    }
+
+    /// Grade a typed answer; fractions must be in lowest terms, while
+    /// decimals may carry trailing zeros.
+    pub fn check_input(&self, input: &str) -> bool {
+        match &self.kind {
+            ProblemKind::Fraction(fraction) => Fraction::parse(input) == Some(fraction.answer),
+            ProblemKind::Decimal(_) | ProblemKind::Percent(_) => {
+                Decimal::parse(input) == self.decimal_answer()
+            }
+            _ => input.trim().parse::<i32>().is_ok_and(|answer| self.check(answer)),
+        }
     }
 }
 
-/// Generate a random problem using TRNG.
-pub fn generate(rng: &Rng, operation: Operation, difficulty: Difficulty) -> Problem {
-    let (min, max) = difficulty.operand_range();
+/// An operand after an operator, parenthesised if negative: `7 - (-3)`.
+fn right_operand(n: i32) -> String {
+    if n < 0 { format!("({})", n) } else { format!("{}", n) }
+}
 
+/// Generate a random problem.
+pub fn generate<R: RandomSource + ?Sized>(rng: &R, operation: Operation, difficulty: Difficulty) -> Problem {
+    generate_in_range(rng, operation, difficulty.operand_range())
+}
+
+/// Generate a random problem with operands (or, for division, the divisor
+/// and answer) in `min..=max`. Percentages scale their bases and
+/// percentages from the range; see `percent::generate`. Powers, and the
+/// perfect powers under a root, stay within `4 * max * max`, which keeps
+/// them to 7 digits at most.
+pub fn generate_in_range<R: RandomSource + ?Sized>(
+    rng: &R,
+    operation: Operation,
+    (min, max): (u32, u32),
+) -> Problem {
     match operation {
         Operation::Add => {
             let a = rng.range_inclusive(min, max) as i32;
             let b = rng.range_inclusive(min, max) as i32;
-            Problem { a, b, operation, answer: a + b }
+            Problem::new(a, b, operation, a + b)
         }
         Operation::Subtract => {
             let mut a = rng.range_inclusive(min, max) as i32;
@@ -119,12 +457,12 @@ pub fn generate(rng: &Rng, operation: Operation, difficulty: Difficulty) -> Prob
             if b > a {
                 core::mem::swap(&mut a, &mut b);
             }
-            Problem { a, b, operation, answer: a - b }
+            Problem::new(a, b, operation, a - b)
         }
         Operation::Multiply => {
             let a = rng.range_inclusive(min, max) as i32;
             let b = rng.range_inclusive(min, max) as i32;
-            Problem { a, b, operation, answer: a * b }
+            Problem::new(a, b, operation, a * b)
         }
         Operation::Divide => {
             // Generate answer and divisor, then compute dividend
@@ -132,14 +470,262 @@ pub fn generate(rng: &Rng, operation: Operation, difficulty: Difficulty) -> Prob
             let answer = rng.range_inclusive(min, max) as i32;
             let b = rng.range_inclusive(min, max) as i32;
             let a = answer * b;
-            Problem { a, b, operation, answer }
+            Problem::new(a, b, operation, answer)
+        }
+        Operation::Percent => {
+            Problem::of_kind(operation, ProblemKind::Percent(percent::generate(rng, (min, max))))
+        }
+        Operation::Square | Operation::Cube | Operation::Power => {
+            let limit = 4 * max * max;
+            // Exponent first, or nearly every power would be a square
+            let exp = match operation {
+                Operation::Square => 2,
+                Operation::Cube => 3,
+                _ => rng.range_inclusive(2, limit.ilog2()),
+            };
+            let low = if operation == Operation::Power { 2 } else { min };
+            let a = rng.range_inclusive(low, root(limit, exp).clamp(low, max)) as i32;
+            Problem::new(a, exp as i32, operation, operation.apply(a, exp as i32))
+        }
+        Operation::SquareRoot | Operation::CubeRoot => {
+            let index = if operation == Operation::SquareRoot { 2 } else { 3 };
+            let answer = rng.range_inclusive(min, root(4 * max * max, index).clamp(min, max)) as i32;
+            Problem::new(answer.pow(index), index as i32, operation, answer)
         }
     }
 }
 
+/// One of `ops`, chosen uniformly.
+pub fn pick_op<R: RandomSource + ?Sized>(rng: &R, ops: &[Operation]) -> Operation {
+    ops[rng.range(ops.len() as u32) as usize]
+}
+
 /// Generate a problem with a randomly selected operation.
-pub fn generate_mixed(rng: &Rng, difficulty: Difficulty) -> Problem {
-    let ops = Operation::all();
-    let idx = rng.range(ops.len() as u32) as usize;
-    generate(rng, ops[idx], difficulty)
+pub fn generate_mixed<R: RandomSource + ?Sized>(rng: &R, difficulty: Difficulty) -> Problem {
+    generate_mixed_in_range(rng, difficulty.operand_range())
+}
+
+/// `generate_mixed` over an explicit operand range.
+pub fn generate_mixed_in_range<R: RandomSource + ?Sized>(rng: &R, range: (u32, u32)) -> Problem {
+    generate_in_range(rng, pick_op(rng, Operation::all()), range)
+}
+
+/// Generate a problem for one of the four operations whose operands (or,
+/// for division, the answer) are anywhere in `-max..=max`, zero included,
+/// with a divisor from the same range that is never zero. Differences
+/// aren't kept positive; anything but the four operations is left to
+/// `generate_in_range`.
+pub fn generate_signed<R: RandomSource + ?Sized>(
+    rng: &R,
+    operation: Operation,
+    (min, max): (u32, u32),
+) -> Problem {
+    let pick = || rng.range_inclusive(0, 2 * max) as i32 - max as i32;
+    match operation {
+        Operation::Add | Operation::Subtract | Operation::Multiply => {
+            let (a, b) = (pick(), pick());
+            Problem::new(a, b, operation, operation.apply(a, b))
+        }
+        Operation::Divide => {
+            let answer = pick();
+            let b = rng.range_inclusive(1, max) as i32;
+            let b = if rng.range(2) == 0 { -b } else { b };
+            Problem::new(answer * b, b, operation, answer)
+        }
+        _ => generate_in_range(rng, operation, (min, max)),
+    }
+}
+
+/// `generate_signed` with a randomly selected operation.
+pub fn generate_signed_mixed<R: RandomSource + ?Sized>(rng: &R, range: (u32, u32)) -> Problem {
+    generate_signed(rng, pick_op(rng, Operation::all()), range)
+}
+
+/// Generate a multi-operand expression problem.
+pub fn generate_expression<R: RandomSource + ?Sized>(rng: &R, difficulty: Difficulty) -> Problem {
+    generate_expression_in_range(rng, difficulty.operand_range())
+}
+
+/// `generate_expression` over an explicit operand range.
+pub fn generate_expression_in_range<R: RandomSource + ?Sized>(rng: &R, range: (u32, u32)) -> Problem {
+    let (expression, answer) = expression::generate(rng, range);
+    let operation = expression
+        .tokens
+        .iter()
+        .find_map(|token| match token {
+            expression::Token::Op(op) => Some(*op),
+            _ => None,
+        })
+        .unwrap_or(Operation::Add);
+    Problem::of_kind(operation, ProblemKind::Expression { expression, answer })
+}
+
+/// Generate a fraction problem.
+pub fn generate_fraction<R: RandomSource + ?Sized>(rng: &R, operation: Operation, difficulty: Difficulty) -> Problem {
+    Problem::of_kind(operation, ProblemKind::Fraction(fraction::generate(rng, operation, difficulty)))
+}
+
+/// A fraction problem with a randomly selected operation.
+pub fn generate_fraction_mixed<R: RandomSource + ?Sized>(rng: &R, difficulty: Difficulty) -> Problem {
+    generate_fraction(rng, pick_op(rng, Operation::all()), difficulty)
+}
+
+/// Generate a decimal problem.
+pub fn generate_decimal<R: RandomSource + ?Sized>(rng: &R, operation: Operation, difficulty: Difficulty) -> Problem {
+    Problem::of_kind(operation, ProblemKind::Decimal(decimal::generate(rng, operation, difficulty)))
+}
+
+/// A decimal problem with a randomly selected operation.
+pub fn generate_decimal_mixed<R: RandomSource + ?Sized>(rng: &R, difficulty: Difficulty) -> Problem {
+    generate_decimal(rng, pick_op(rng, Operation::all()), difficulty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SeededRng;
+
+    #[test]
+    fn seeded_sequence_is_reproducible() {
+        let rng = SeededRng::new(2024);
+        let seq: alloc::vec::Vec<String> = (0..4)
+            .map(|_| generate_mixed(&rng, Difficulty::Medium).display_with_answer())
+            .collect();
+        assert_eq!(seq, ["20 / 5 = 4", "16 + 17 = 33", "12 - 7 = 5", "11 x 13 = 143"]);
+
+        let rng = SeededRng::new(7);
+        let seq: alloc::vec::Vec<String> = (0..3)
+            .map(|_| generate(&rng, Operation::Multiply, Difficulty::Easy).display_with_answer())
+            .collect();
+        assert_eq!(seq, ["7 x 6 = 42", "2 x 2 = 4", "6 x 8 = 48"]);
+    }
+
+    #[test]
+    fn division_is_exact_and_subtraction_non_negative() {
+        let rng = SeededRng::new(99);
+        for _ in 0..500 {
+            let (a, b, answer) = generate(&rng, Operation::Divide, Difficulty::Hard).arithmetic().unwrap();
+            assert_eq!(a, answer * b);
+            let (_, _, answer) = generate(&rng, Operation::Subtract, Difficulty::Hard).arithmetic().unwrap();
+            assert!(answer >= 0);
+        }
+    }
+
+    #[test]
+    fn blanks_display_and_check_the_missing_operand() {
+        let mut p = generate(&SeededRng::new(7), Operation::Multiply, Difficulty::Easy);
+        assert_eq!(p.display(), "7 x 6 = ?");
+        p.blank = Blank::Left;
+        assert_eq!(p.display(), "? x 6 = 42");
+        assert!(p.check(7) && !p.check(42));
+        p.blank = Blank::Right;
+        assert_eq!(p.display(), "7 x ? = 42");
+        assert!(p.check(6));
+        assert_eq!(p.display_with_answer(), "7 x 6 = 42");
+    }
+
+    #[test]
+    fn zero_factors_and_dividends_leave_the_blank_open() {
+        let blanked = |a, b, operation, blank| {
+            let mut p = Problem::new(a, b, operation, operation.apply(a, b));
+            p.blank = blank;
+            p.has_unique_blank()
+        };
+        assert!(!blanked(0, 5, Operation::Multiply, Blank::Right));
+        assert!(!blanked(5, 0, Operation::Multiply, Blank::Left));
+        assert!(!blanked(0, -3, Operation::Divide, Blank::Right));
+        assert!(blanked(0, 5, Operation::Multiply, Blank::Left));
+        assert!(blanked(0, -3, Operation::Divide, Blank::Left));
+        assert!(blanked(0, 5, Operation::Add, Blank::Right));
+        assert!(blanked(6, -3, Operation::Divide, Blank::Right));
+    }
+
+    #[test]
+    fn powers_and_roots_display_and_fit_the_answer_buffer() {
+        assert_eq!(Problem::new(7, 2, Operation::Square, 49).display(), "7\u{b2} = ?");
+        assert_eq!(Problem::new(4, 3, Operation::Cube, 64).display(), "4\u{b3} = ?");
+        assert_eq!(Problem::new(2, 10, Operation::Power, 1024).display(), "2^10 = ?");
+        assert_eq!(Problem::new(144, 2, Operation::SquareRoot, 12).display(), "\u{221a}144 = ?");
+        assert_eq!(Problem::new(27, 3, Operation::CubeRoot, 3).display(), "\u{b3}\u{221a}27 = ?");
+        let ops = [
+            Operation::Square,
+            Operation::Cube,
+            Operation::Power,
+            Operation::SquareRoot,
+            Operation::CubeRoot,
+        ];
+        let rng = SeededRng::new(24);
+        for range in [(1, 9), (2, 19), (2, 49), (2, 999)] {
+            for _ in 0..200 {
+                let p = generate_in_range(&rng, ops[rng.range(5) as usize], range);
+                assert!(!p.is_fact());
+                let (a, b, answer) = p.arithmetic().unwrap();
+                assert!(b >= 2 && answer >= 1);
+                assert_eq!(p.operation.apply(a, b), answer, "{}", p.display_with_answer());
+                if matches!(p.operation, Operation::SquareRoot | Operation::CubeRoot) {
+                    assert_eq!(answer.pow(b as u32), a);
+                }
+                assert!(format!("{}", answer).len() <= 8);
+            }
+        }
+    }
+
+    #[test]
+    fn signed_problems_take_both_signs_and_stay_exact() {
+        assert_eq!(Problem::new(7, -3, Operation::Subtract, 10).display(), "7 - (-3) = ?");
+        assert_eq!(Problem::new(-4, -6, Operation::Multiply, 24).display(), "-4 x (-6) = ?");
+        let mut p = Problem::new(-12, -3, Operation::Divide, 4);
+        p.blank = Blank::Left;
+        assert_eq!(p.display(), "? / (-3) = 4");
+
+        let rng = SeededRng::new(25);
+        let mut negative_differences = 0;
+        let mut zeros = 0;
+        for _ in 0..500 {
+            let p = generate_signed_mixed(&rng, (2, 19));
+            let (a, b, answer) = p.arithmetic().unwrap();
+            assert!((-19..=19).contains(&b));
+            assert!(p.operation != Operation::Divide || b != 0);
+            assert_eq!(p.operation.apply(a, b), answer, "{}", p.display_with_answer());
+            if p.operation == Operation::Subtract && answer < 0 {
+                negative_differences += 1;
+            }
+            zeros += (a == 0 || b == 0) as u32;
+        }
+        assert!(negative_differences > 20);
+        assert!(zeros > 0);
+    }
+
+    #[test]
+    fn problems_keep_their_stored_layout() {
+        let json = serde_json::to_string(&Problem::new(7, 8, Operation::Multiply, 56)).unwrap();
+        assert_eq!(json, r#"{"a":7,"b":8,"operation":"Multiply","answer":56,"blank":"Answer"}"#);
+        let p: Problem = serde_json::from_str(r#"{"a":3,"b":4,"operation":"Add","answer":7}"#).unwrap();
+        assert_eq!(p.display_with_answer(), "3 + 4 = 7");
+
+        let rng = SeededRng::new(1);
+        for p in [
+            generate_expression(&rng, Difficulty::Easy),
+            generate_fraction(&rng, Operation::Add, Difficulty::Easy),
+            generate_decimal(&rng, Operation::Multiply, Difficulty::Easy),
+            generate(&rng, Operation::Percent, Difficulty::Easy),
+        ] {
+            let back: Problem = serde_json::from_str(&serde_json::to_string(&p).unwrap()).unwrap();
+            assert_eq!(back.display_with_answer(), p.display_with_answer());
+            assert_eq!(core::mem::discriminant(&back.kind), core::mem::discriminant(&p.kind));
+        }
+    }
+
+    #[test]
+    fn escalated_ranges_grow_and_stay_capped() {
+        assert_eq!(Difficulty::Easy.operand_range_at(0), (1, 9));
+        assert_eq!(Difficulty::Easy.operand_range_at(2), (1, 18));
+        assert_eq!(Difficulty::Hard.operand_range_at(100), (2, 999));
+        let rng = SeededRng::new(3);
+        for _ in 0..200 {
+            let p = generate_mixed_in_range(&rng, Difficulty::Medium.operand_range_at(3));
+            let (_, b, _) = p.arithmetic().unwrap();
+            assert!((2..=47).contains(&b));
+        }
+    }
 }
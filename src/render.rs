@@ -0,0 +1,175 @@
+//! Drawing backends for Math Drill.
+//!
+//! `ui` draws every screen through the `Renderer` trait. `GamRenderer`
+//! forwards to the GAM on device; `TextRenderer` records each call as a
+//! line of text so screens can be snapshot-tested on a host.
+
+extern crate alloc;
+#[cfg(test)]
+use alloc::{format, string::String, vec::Vec};
+
+/// Font styles the UI uses (a subset of the GAM's `GlyphStyle`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlyphStyle {
+    Small,
+    Regular,
+    Bold,
+    Large,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Light,
+    Dark,
+}
+
+/// Inclusive pixel rectangle, top-left to bottom-right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x0: i16,
+    pub y0: i16,
+    pub x1: i16,
+    pub y1: i16,
+}
+
+impl Rect {
+    pub fn new(x0: i16, y0: i16, x1: i16, y1: i16) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+}
+
+pub trait Renderer {
+    /// Rectangle filled with `fill`, outlined `stroke_width` px in `stroke`.
+    fn rect(&mut self, rect: Rect, fill: Color, stroke: Color, stroke_width: i16);
+
+    fn line(&mut self, x0: i16, y0: i16, x1: i16, y1: i16, color: Color, width: i16);
+
+    /// Borderless text box; `invert` draws light text for dark backgrounds.
+    fn text(&mut self, bounds: Rect, text: &str, style: GlyphStyle, invert: bool);
+
+    /// Push the finished frame to the screen.
+    fn flush(&mut self);
+}
+
+/// Records draw calls, one per line, for golden-file comparisons.
+#[cfg(test)]
+#[derive(Default)]
+pub struct TextRenderer {
+    pub ops: Vec<String>,
+}
+
+#[cfg(test)]
+impl TextRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> String {
+        let mut out = self.ops.join("\n");
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+impl Renderer for TextRenderer {
+    fn rect(&mut self, r: Rect, fill: Color, stroke: Color, stroke_width: i16) {
+        self.ops.push(format!(
+            "rect ({},{})-({},{}) fill={:?} stroke={:?}/{}",
+            r.x0, r.y0, r.x1, r.y1, fill, stroke, stroke_width
+        ));
+    }
+
+    fn line(&mut self, x0: i16, y0: i16, x1: i16, y1: i16, color: Color, width: i16) {
+        self.ops.push(format!(
+            "line ({},{})-({},{}) {:?}/{}",
+            x0, y0, x1, y1, color, width
+        ));
+    }
+
+    fn text(&mut self, b: Rect, text: &str, style: GlyphStyle, invert: bool) {
+        self.ops.push(format!(
+            "text ({},{})-({},{}) {:?}{} {:?}",
+            b.x0,
+            b.y0,
+            b.x1,
+            b.y1,
+            style,
+            if invert { " inverted" } else { "" },
+            text
+        ));
+    }
+
+    fn flush(&mut self) {
+        self.ops.push(String::from("flush"));
+    }
+}
+
+#[cfg(target_os = "xous")]
+pub use gam_renderer::GamRenderer;
+
+#[cfg(target_os = "xous")]
+mod gam_renderer {
+    use super::*;
+    use gam::*;
+    use graphics_server::{DrawStyle, PixelColor, Point, Rectangle, TextBounds};
+
+    pub struct GamRenderer<'a> {
+        gam: &'a Gam,
+        canvas: Canvas,
+    }
+
+    impl<'a> GamRenderer<'a> {
+        pub fn new(gam: &'a Gam, canvas: Canvas) -> Self {
+            Self { gam, canvas }
+        }
+    }
+
+    fn pixel(color: Color) -> PixelColor {
+        match color {
+            Color::Light => PixelColor::Light,
+            Color::Dark => PixelColor::Dark,
+        }
+    }
+
+    fn glyph(style: GlyphStyle) -> graphics_server::api::GlyphStyle {
+        match style {
+            GlyphStyle::Small => graphics_server::api::GlyphStyle::Small,
+            GlyphStyle::Regular => graphics_server::api::GlyphStyle::Regular,
+            GlyphStyle::Bold => graphics_server::api::GlyphStyle::Bold,
+            GlyphStyle::Large => graphics_server::api::GlyphStyle::Large,
+        }
+    }
+
+    fn rectangle(r: Rect) -> Rectangle {
+        Rectangle::new(Point::new(r.x0, r.y0), Point::new(r.x1, r.y1))
+    }
+
+    impl Renderer for GamRenderer<'_> {
+        fn rect(&mut self, r: Rect, fill: Color, stroke: Color, stroke_width: i16) {
+            self.gam.draw_rectangle(self.canvas, rectangle(r).style(
+                DrawStyle::new(pixel(fill), pixel(stroke), stroke_width),
+            )).ok();
+        }
+
+        fn line(&mut self, x0: i16, y0: i16, x1: i16, y1: i16, color: Color, width: i16) {
+            self.gam.draw_line(self.canvas, Point::new(x0, y0), Point::new(x1, y1),
+                DrawStyle::new(pixel(color), pixel(color), width),
+            ).ok();
+        }
+
+        fn text(&mut self, bounds: Rect, text: &str, style: GlyphStyle, invert: bool) {
+            self.gam.draw_textview(
+                self.canvas,
+                tv::TextView::new(TextBounds::BoundingBox(rectangle(bounds)), text)
+                    .style(glyph(style))
+                    .draw_border(false)
+                    .invert(invert),
+            ).ok();
+        }
+
+        fn flush(&mut self) {
+            self.gam.redraw().ok();
+        }
+    }
+}
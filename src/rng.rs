@@ -1,24 +1,22 @@
-//! TRNG wrapper — same pattern as Minesweeper / Decision Engine.
+//! Random sources for Math Drill.
+//!
+//! `RandomSource` is what problem generation draws from. `Rng` wraps the
+//! TRNG (same pattern as Minesweeper / Decision Engine) and is the default;
+//! `SeededRng` is a deterministic xorshift64* generator so a session can be
+//! replayed from its seed and tests can assert exact problem sequences.
 
 extern crate alloc;
+use core::cell::Cell;
 
-pub struct Rng {
-    trng: trng::Trng,
-}
-
-impl Rng {
-    pub fn new(xns: &xous_names::XousNames) -> Self {
-        Self {
-            trng: trng::Trng::new(xns).expect("can't connect to TRNG"),
-        }
-    }
+pub trait RandomSource {
+    fn u32(&self) -> u32;
 
-    pub fn u32(&self) -> u32 {
-        self.trng.get_u32().unwrap_or(0)
+    fn u64(&self) -> u64 {
+        ((self.u32() as u64) << 32) | self.u32() as u64
     }
 
     /// Random number in range [0, max) with rejection sampling.
-    pub fn range(&self, max: u32) -> u32 {
+    fn range(&self, max: u32) -> u32 {
         if max <= 1 {
             return 0;
         }
@@ -32,10 +30,139 @@ impl Rng {
     }
 
     /// Random number in range [min, max] inclusive.
-    pub fn range_inclusive(&self, min: u32, max: u32) -> u32 {
+    fn range_inclusive(&self, min: u32, max: u32) -> u32 {
         if max <= min {
             return min;
         }
         min + self.range(max - min + 1)
     }
 }
+
+/// Fisher–Yates shuffle of `items` in place.
+pub fn shuffle<R: RandomSource + ?Sized, T>(rng: &R, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        let j = rng.range(i as u32 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(target_os = "xous")]
+pub struct Rng {
+    trng: trng::Trng,
+}
+
+#[cfg(target_os = "xous")]
+impl Rng {
+    pub fn new(xns: &xous_names::XousNames) -> Self {
+        Self {
+            trng: trng::Trng::new(xns).expect("can't connect to TRNG"),
+        }
+    }
+}
+
+#[cfg(target_os = "xous")]
+impl RandomSource for Rng {
+    fn u32(&self) -> u32 {
+        self.trng.get_u32().unwrap_or(0)
+    }
+}
+
+/// Deterministic generator; the same seed always yields the same sequence.
+pub struct SeededRng {
+    seed: u64,
+    state: Cell<u64>,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 scramble so small or zero seeds still give a
+        // well-mixed, non-zero xorshift state
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self {
+            seed,
+            state: Cell::new(if z == 0 { 1 } else { z }),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Current position in the sequence, for `resume`.
+    pub fn state(&self) -> u64 {
+        self.state.get()
+    }
+
+    /// Continue a generator from a saved `seed()` and `state()`.
+    pub fn resume(seed: u64, state: u64) -> Self {
+        Self {
+            seed,
+            state: Cell::new(if state == 0 { 1 } else { state }),
+        }
+    }
+}
+
+impl RandomSource for SeededRng {
+    fn u32(&self) -> u32 {
+        let mut x = self.state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state.set(x);
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let a = SeededRng::new(42);
+        let b = SeededRng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.u32(), b.u32());
+        }
+    }
+
+    #[test]
+    fn resumed_generator_continues_the_sequence() {
+        let a = SeededRng::new(42);
+        a.u32();
+        let b = SeededRng::resume(a.seed(), a.state());
+        assert_eq!(b.seed(), 42);
+        for _ in 0..10 {
+            assert_eq!(a.u32(), b.u32());
+        }
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        let a = SeededRng::new(1);
+        let b = SeededRng::new(2);
+        assert!((0..8).any(|_| a.u32() != b.u32()));
+    }
+
+    #[test]
+    fn shuffle_is_a_permutation() {
+        let rng = SeededRng::new(5);
+        let mut items = [1, 2, 3, 4, 5, 6, 7, 8];
+        shuffle(&rng, &mut items);
+        assert_ne!(items, [1, 2, 3, 4, 5, 6, 7, 8]);
+        items.sort_unstable();
+        assert_eq!(items, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn range_inclusive_stays_in_bounds() {
+        let rng = SeededRng::new(0);
+        for _ in 0..1000 {
+            let v = rng.range_inclusive(2, 19);
+            assert!((2..=19).contains(&v));
+        }
+    }
+}
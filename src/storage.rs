@@ -1,19 +1,225 @@
-//! PDDB storage for Math Drill.
+//! Score storage for Math Drill.
 //!
 //! Dictionary: mathdrill.stats
-//! Keys: best_{difficulty} — JSON with best streak, score, avg time
+//! Keys: best_{difficulty}_{length} — JSON with best streak, score, avg time
+//!       (10-problem bests from before lengths were configurable live in
+//!       the legacy key best_{difficulty})
+//!       sprint_{difficulty}_{seconds} — the same, for a Sprint of that length
+//!       survival_{difficulty} — the same for the longest Survival run
+//!       settings — JSON `Settings`
+//!       in_progress — JSON `SavedSession`, a paused session to offer at launch
+//!
+//! Dictionary: mathdrill.history
+//! Keys: meta — JSON with the next session sequence number
+//!       session_{seq} — JSON `SessionRecord`, the newest `HISTORY_LIMIT` kept
+//!
+//! Dictionary: mathdrill.facts
+//! Keys: facts_{operation} — JSON list of `[a, b, FactStats]` per fact
+//!
+//! `ScoreStore` is the raw key/value backend: `PddbStore` on device and
+//! `MemoryStore` on a host build. `Storage` layers the typed records on
+//! top and reports (rather than swallows) backend failures. It only
+//! replaces a key it has read successfully, so a write made before the
+//! PDDB mounts can't clobber records the app never got to see.
 
 extern crate alloc;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::cell::Cell;
 
-use crate::problems::Difficulty;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{SavedSession, SessionKind};
+use crate::facts::{FactStats, FactTable};
+use crate::history::SessionRecord;
+use crate::problems::{Difficulty, Operation};
 
 const DICT: &str = "mathdrill.stats";
+const HISTORY_DICT: &str = "mathdrill.history";
+const FACTS_DICT: &str = "mathdrill.facts";
 
-pub struct Storage {
+/// Number of sessions kept in history; older ones are deleted on append.
+const HISTORY_LIMIT: u32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StoreError {
+    /// The PDDB isn't mounted (or has been unmounted) — nothing is persisted.
+    NotMounted,
+    /// The backend failed to read or write the key.
+    #[cfg(target_os = "xous")]
+    Io,
+    /// The key hasn't been read yet, so writing it could lose its contents.
+    NotLoaded,
+}
+
+/// Raw key/value backend behind `Storage`.
+pub trait ScoreStore {
+    /// Read a whole key; `Ok(None)` if it doesn't exist or is empty.
+    fn read_key(&mut self, dict: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError>;
+
+    /// Replace the contents of a key, creating it if needed.
+    fn write_key(&mut self, dict: &str, key: &str, data: &[u8]) -> Result<(), StoreError>;
+
+    /// Remove a key; deleting a key that doesn't exist is not an error.
+    fn delete_key(&mut self, dict: &str, key: &str) -> Result<(), StoreError>;
+
+    /// Whether writes currently survive an app restart.
+    fn is_persistent(&self) -> bool;
+
+    /// Whether keys can be read and written right now.
+    fn is_mounted(&self) -> bool;
+}
+
+/// Volatile backend for host builds and tests.
+pub struct MemoryStore {
+    entries: BTreeMap<(String, String), Vec<u8>>,
+    /// Cleared to stand in for a locked PDDB; shared so a test can mount it later.
+    mounted: Rc<Cell<bool>>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            mounted: Rc::new(Cell::new(true)),
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store that fails every call until the returned flag is set.
+    #[cfg(test)]
+    pub fn unmounted() -> (Self, Rc<Cell<bool>>) {
+        let store = Self::default();
+        store.mounted.set(false);
+        let mounted = store.mounted.clone();
+        (store, mounted)
+    }
+
+    fn check_mounted(&self) -> Result<(), StoreError> {
+        if self.mounted.get() {
+            Ok(())
+        } else {
+            Err(StoreError::NotMounted)
+        }
+    }
+}
+
+impl ScoreStore for MemoryStore {
+    fn read_key(&mut self, dict: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        self.check_mounted()?;
+        Ok(self
+            .entries
+            .get(&(dict.to_string(), key.to_string()))
+            .filter(|buf| !buf.is_empty())
+            .cloned())
+    }
+
+    fn write_key(&mut self, dict: &str, key: &str, data: &[u8]) -> Result<(), StoreError> {
+        self.check_mounted()?;
+        self.entries
+            .insert((dict.to_string(), key.to_string()), data.to_vec());
+        Ok(())
+    }
+
+    fn delete_key(&mut self, dict: &str, key: &str) -> Result<(), StoreError> {
+        self.check_mounted()?;
+        self.entries.remove(&(dict.to_string(), key.to_string()));
+        Ok(())
+    }
+
+    fn is_persistent(&self) -> bool {
+        false
+    }
+
+    fn is_mounted(&self) -> bool {
+        self.mounted.get()
+    }
+}
+
+#[cfg(target_os = "xous")]
+pub struct PddbStore {
     pddb: pddb::Pddb,
 }
 
+#[cfg(target_os = "xous")]
+impl PddbStore {
+    /// Connect without waiting for a mount; every call re-checks, so
+    /// scores start saving as soon as the user unlocks the PDDB.
+    pub fn new() -> Self {
+        Self {
+            pddb: pddb::Pddb::new(),
+        }
+    }
+}
+
+#[cfg(target_os = "xous")]
+impl ScoreStore for PddbStore {
+    fn read_key(&mut self, dict: &str, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        if !self.pddb.is_mounted_nonblocking() {
+            return Err(StoreError::NotMounted);
+        }
+        let mut handle = match self
+            .pddb
+            .get(dict, key, None, false, false, None, None::<fn()>)
+        {
+            Ok(handle) => handle,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(_) => return Err(StoreError::Io),
+        };
+        let mut buf = Vec::new();
+        use std::io::Read;
+        handle.read_to_end(&mut buf).map_err(|_| StoreError::Io)?;
+        if buf.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(buf))
+        }
+    }
+
+    fn write_key(&mut self, dict: &str, key: &str, data: &[u8]) -> Result<(), StoreError> {
+        if !self.pddb.is_mounted_nonblocking() {
+            return Err(StoreError::NotMounted);
+        }
+        let mut handle = self
+            .pddb
+            .get(dict, key, None, true, true, Some(data.len()), None::<fn()>)
+            .map_err(|_| StoreError::Io)?;
+        use std::io::{Seek, Write};
+        handle.seek(std::io::SeekFrom::Start(0)).map_err(|_| StoreError::Io)?;
+        handle.write_all(data).map_err(|_| StoreError::Io)?;
+        handle.set_len(data.len() as u64).map_err(|_| StoreError::Io)?;
+        self.pddb.sync().map_err(|_| StoreError::Io)
+    }
+
+    fn delete_key(&mut self, dict: &str, key: &str) -> Result<(), StoreError> {
+        if !self.pddb.is_mounted_nonblocking() {
+            return Err(StoreError::NotMounted);
+        }
+        match self.pddb.delete_key(dict, key, None) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(_) => return Err(StoreError::Io),
+        }
+        self.pddb.sync().map_err(|_| StoreError::Io)
+    }
+
+    fn is_persistent(&self) -> bool {
+        self.pddb.is_mounted_nonblocking()
+    }
+
+    fn is_mounted(&self) -> bool {
+        self.pddb.is_mounted_nonblocking()
+    }
+}
+
 /// Best stats for a difficulty level.
 #[derive(Debug, Clone)]
 pub struct BestStats {
@@ -23,44 +229,113 @@ pub struct BestStats {
     pub avg_ms: u32,
 }
 
+/// Menu choices remembered between launches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    pub session_length: usize,
+    #[serde(default)]
+    pub session_kind: SessionKind,
+    /// Per-problem time limit in seconds for Easy, Medium, Hard (0 = none).
+    #[serde(default)]
+    pub problem_limit_secs: [u32; 3],
+}
+
+pub struct Storage {
+    backend: Box<dyn ScoreStore>,
+    /// Keys read without a backend error (found or not), the only ones
+    /// `write_json` will replace.
+    read_ok: BTreeSet<(String, String)>,
+}
+
 impl Storage {
-    pub fn new() -> Result<Self, ()> {
-        let pddb = pddb::Pddb::new();
-        pddb.is_mounted_blocking();
-        Ok(Self { pddb })
+    pub fn new(backend: Box<dyn ScoreStore>) -> Self {
+        Self {
+            backend,
+            read_ok: BTreeSet::new(),
+        }
     }
 
-    fn read_key(&mut self, key: &str) -> Option<Vec<u8>> {
-        let mut handle = self
-            .pddb
-            .get(DICT, key, None, false, false, None, None::<fn()>)
-            .ok()?;
-        let mut buf = Vec::new();
-        use std::io::Read;
-        handle.read_to_end(&mut buf).ok()?;
-        if buf.is_empty() {
-            None
+    pub fn is_persistent(&self) -> bool {
+        self.backend.is_persistent()
+    }
+
+    pub fn is_mounted(&self) -> bool {
+        self.backend.is_mounted()
+    }
+
+    fn read_json<T: serde::de::DeserializeOwned>(&mut self, dict: &str, key: &str) -> Option<T> {
+        let result = self.backend.read_key(dict, key);
+        if result.is_ok() {
+            self.read_ok.insert((dict.to_string(), key.to_string()));
+        }
+        match result {
+            Ok(Some(buf)) => match serde_json::from_slice(&buf) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    log::warn!("corrupt record {}/{}", dict, key);
+                    None
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                log::warn!("can't read {}/{}: {:?}", dict, key, e);
+                None
+            }
+        }
+    }
+
+    fn check_read(&self, dict: &str, key: &str) -> Result<(), StoreError> {
+        if self.read_ok.contains(&(dict.to_string(), key.to_string())) {
+            Ok(())
         } else {
-            Some(buf)
+            log::warn!("not writing {}/{}: never read", dict, key);
+            Err(StoreError::NotLoaded)
         }
     }
 
-    fn write_key(&mut self, key: &str, data: &[u8]) {
-        if let Ok(mut handle) = self.pddb.get(
-            DICT, key, None, true, true, Some(data.len()), None::<fn()>,
-        ) {
-            use std::io::{Seek, Write};
-            handle.seek(std::io::SeekFrom::Start(0)).ok();
-            handle.write_all(data).ok();
-            handle.set_len(data.len() as u64).ok();
+    fn write_json<T: serde::Serialize>(&mut self, dict: &str, key: &str, value: &T) -> Result<(), StoreError> {
+        self.check_read(dict, key)?;
+        self.put_json(dict, key, value)
+    }
+
+    /// `write_json` without the read check, for keys known to be new.
+    fn put_json<T: serde::Serialize>(&mut self, dict: &str, key: &str, value: &T) -> Result<(), StoreError> {
+        let data = serde_json::to_vec(value).unwrap_or_default();
+        self.backend.write_key(dict, key, &data).inspect_err(|e| {
+            log::warn!("can't write {}/{}: {:?}", dict, key, e);
+        })
+    }
+
+    pub fn load_best(&mut self, diff: &Difficulty, length: usize) -> Option<BestStats> {
+        let key = alloc::format!("best_{}_{}", diff.key(), length);
+        match self.read_best(&key) {
+            None if length == 10 => self.read_best(&alloc::format!("best_{}", diff.key())),
+            best => best,
         }
-        self.pddb.sync().ok();
     }
 
-    pub fn load_best(&mut self, diff: &Difficulty) -> Option<BestStats> {
-        let key = alloc::format!("best_{}", diff.key());
-        let buf = self.read_key(&key)?;
-        let json: serde_json::Value = serde_json::from_slice(&buf).ok()?;
+    pub fn save_best(&mut self, diff: &Difficulty, length: usize, stats: &BestStats) -> Result<(), StoreError> {
+        self.write_best(&alloc::format!("best_{}_{}", diff.key(), length), stats)
+    }
+
+    pub fn load_sprint_best(&mut self, diff: &Difficulty, secs: u32) -> Option<BestStats> {
+        self.read_best(&alloc::format!("sprint_{}_{}", diff.key(), secs))
+    }
+
+    pub fn save_sprint_best(&mut self, diff: &Difficulty, secs: u32, stats: &BestStats) -> Result<(), StoreError> {
+        self.write_best(&alloc::format!("sprint_{}_{}", diff.key(), secs), stats)
+    }
+
+    pub fn load_survival_best(&mut self, diff: &Difficulty) -> Option<BestStats> {
+        self.read_best(&alloc::format!("survival_{}", diff.key()))
+    }
+
+    pub fn save_survival_best(&mut self, diff: &Difficulty, stats: &BestStats) -> Result<(), StoreError> {
+        self.write_best(&alloc::format!("survival_{}", diff.key()), stats)
+    }
+
+    fn read_best(&mut self, key: &str) -> Option<BestStats> {
+        let json: serde_json::Value = self.read_json(DICT, key)?;
         Some(BestStats {
             streak: json.get("streak").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
             correct: json.get("correct").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
@@ -69,15 +344,120 @@ impl Storage {
         })
     }
 
-    pub fn save_best(&mut self, diff: &Difficulty, stats: &BestStats) {
-        let key = alloc::format!("best_{}", diff.key());
+    fn write_best(&mut self, key: &str, stats: &BestStats) -> Result<(), StoreError> {
         let json = serde_json::json!({
             "streak": stats.streak,
             "correct": stats.correct,
             "total": stats.total,
             "avg_ms": stats.avg_ms,
         });
-        let data = serde_json::to_vec(&json).unwrap_or_default();
-        self.write_key(&key, &data);
+        self.write_json(DICT, key, &json)
+    }
+
+    pub fn load_settings(&mut self) -> Option<Settings> {
+        self.read_json(DICT, "settings")
+    }
+
+    pub fn save_settings(&mut self, settings: &Settings) -> Result<(), StoreError> {
+        self.write_json(DICT, "settings", settings)
+    }
+
+    pub fn load_session_in_progress(&mut self) -> Option<SavedSession> {
+        self.read_json(DICT, "in_progress")
+    }
+
+    pub fn save_session_in_progress(&mut self, session: &SavedSession) -> Result<(), StoreError> {
+        self.write_json(DICT, "in_progress", session)
+    }
+
+    pub fn clear_session_in_progress(&mut self) -> Result<(), StoreError> {
+        self.check_read(DICT, "in_progress")?;
+        self.backend.delete_key(DICT, "in_progress").inspect_err(|e| {
+            log::warn!("can't delete {}/in_progress: {:?}", DICT, e);
+        })
+    }
+
+    /// Number of sessions ever appended to history (including pruned ones).
+    pub fn history_count(&mut self) -> u32 {
+        let meta: Option<serde_json::Value> = self.read_json(HISTORY_DICT, "meta");
+        meta.and_then(|m| m.get("next").and_then(|v| v.as_u64()))
+            .unwrap_or(0) as u32
+    }
+
+    /// Append a finished session, pruning the oldest beyond `HISTORY_LIMIT`.
+    pub fn append_history(&mut self, record: &SessionRecord) -> Result<(), StoreError> {
+        let seq = self.history_count();
+        // An unread meta key reads as 0 and would put this over session 0
+        self.check_read(HISTORY_DICT, "meta")?;
+        self.put_json(HISTORY_DICT, &alloc::format!("session_{:08}", seq), record)?;
+        self.write_json(HISTORY_DICT, "meta", &serde_json::json!({ "next": seq + 1 }))?;
+        if seq >= HISTORY_LIMIT {
+            let oldest = alloc::format!("session_{:08}", seq - HISTORY_LIMIT);
+            self.backend.delete_key(HISTORY_DICT, &oldest).inspect_err(|e| {
+                log::warn!("can't prune {}/{}: {:?}", HISTORY_DICT, oldest, e);
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Load every operation's fact statistics into `table`.
+    pub fn load_facts(&mut self, table: &mut FactTable) {
+        for op in Operation::all() {
+            let key = alloc::format!("facts_{}", op.key());
+            let facts: Option<Vec<(i32, i32, FactStats)>> = self.read_json(FACTS_DICT, &key);
+            if let Some(facts) = facts {
+                table.import(*op, facts);
+            }
+        }
+    }
+
+    /// Write back the operations whose facts changed since the last save.
+    pub fn save_facts(&mut self, table: &mut FactTable) -> Result<(), StoreError> {
+        let mut result = Ok(());
+        for op in table.take_dirty() {
+            let key = alloc::format!("facts_{}", op.key());
+            if let Err(e) = self.write_json(FACTS_DICT, &key, &table.export(op)) {
+                table.mark_dirty(op);
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Read back one history entry by sequence number.
+    #[cfg(test)]
+    pub fn load_session(&mut self, seq: u32) -> Option<SessionRecord> {
+        self.read_json(HISTORY_DICT, &alloc::format!("session_{:08}", seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::OpMode;
+    use crate::problems::Operation;
+
+    #[test]
+    fn history_keeps_newest_sessions() {
+        let mut st = Storage::new(Box::new(MemoryStore::new()));
+        for i in 0..HISTORY_LIMIT + 3 {
+            let record = SessionRecord {
+                timestamp: i as u64,
+                op_mode: OpMode::Single(Operation::Add),
+                difficulty: Difficulty::Easy,
+                seed: 0,
+                kind: SessionKind::Standard,
+                incomplete: false,
+                attempts: Vec::new(),
+            };
+            st.append_history(&record).unwrap();
+        }
+        assert_eq!(st.history_count(), HISTORY_LIMIT + 3);
+        assert!(st.load_session(2).is_none());
+        assert_eq!(st.load_session(3).unwrap().timestamp, 3);
+        assert_eq!(
+            st.load_session(HISTORY_LIMIT + 2).unwrap().timestamp,
+            (HISTORY_LIMIT + 2) as u64
+        );
     }
 }
@@ -0,0 +1,54 @@
+//! Background tick thread for Math Drill.
+//!
+//! The main loop tells the timer when the app next wants to be woken
+//! (see `MathDrillApp::next_deadline`). The tick thread blocks until a
+//! deadline is armed, sleeps until it passes, and then posts `AppOp::Tick`
+//! to our own server, so timed states advance without a keypress. With no
+//! deadline armed, in the menu or while backgrounded, it doesn't wake.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+pub struct Timer {
+    deadline: Arc<(Mutex<Option<u64>>, Condvar)>,
+}
+
+impl Timer {
+    /// Spawn the tick thread, which sends scalar message `tick_id` to `sid`.
+    pub fn spawn(sid: xous::SID, tick_id: usize) -> Self {
+        let deadline = Arc::new((Mutex::new(None::<u64>), Condvar::new()));
+        let shared = deadline.clone();
+        std::thread::spawn(move || {
+            let tt = ticktimer_server::Ticktimer::new().unwrap();
+            let cid = xous::connect(sid).expect("can't connect to own server");
+            let (lock, armed) = &*shared;
+            let mut pending = lock.lock().unwrap();
+            loop {
+                match *pending {
+                    None => pending = armed.wait(pending).unwrap(),
+                    Some(at) => {
+                        let now = tt.elapsed_ms();
+                        if now < at {
+                            // Woken early if the deadline is moved or disarmed
+                            let wait = Duration::from_millis(at - now);
+                            pending = armed.wait_timeout(pending, wait).unwrap().0;
+                            continue;
+                        }
+                        *pending = None;
+                        drop(pending);
+                        xous::send_message(cid, xous::Message::new_scalar(tick_id, 0, 0, 0, 0)).ok();
+                        pending = lock.lock().unwrap();
+                    }
+                }
+            }
+        });
+        Self { deadline }
+    }
+
+    /// Arm (or disarm, with `None`) the next wake-up, in ticktimer ms.
+    pub fn set_deadline(&self, deadline: Option<u64>) {
+        let (lock, armed) = &*self.deadline;
+        *lock.lock().unwrap() = deadline;
+        armed.notify_one();
+    }
+}
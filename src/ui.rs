@@ -1,262 +1,588 @@
 //! UI rendering for Math Drill.
 //!
 //! Large problem display, answer input, progress bar, results screen.
+//! Everything is drawn through a `Renderer`, so screens render the same
+//! into the GAM or into a text snapshot.
 
 extern crate alloc;
 use alloc::format;
 use alloc::string::String;
 
-use gam::*;
-use graphics_server::api::GlyphStyle;
-use graphics_server::{DrawStyle, PixelColor, Point, Rectangle, TextBounds};
-
 use crate::app::*;
-use crate::problems::Difficulty;
+use crate::facts::{FactKey, GRID_MAX, SLOW_MS};
+use crate::fraction::{self, Fraction, FractionProblem};
+use crate::problems::{Difficulty, ProblemKind};
+use crate::render::{Color, GlyphStyle, Rect, Renderer};
 
 const SCREEN_W: i16 = 336;
+const SCREEN_H: i16 = 536;
 const HEADER_H: i16 = 30;
 const FOOTER_H: i16 = 46;
 const LINE_H: i16 = 22;
+/// Approximate advance of one glyph, for laying out stacked fractions and
+/// wrapping long questions.
+const LARGE_CHAR_W: i16 = 16;
+const BOLD_CHAR_W: i16 = 10;
 
-fn draw_header(gam: &Gam, canvas: Canvas, text: &str) {
-    let header_rect = Rectangle::new(
-        Point::new(0, 0),
-        Point::new(SCREEN_W - 1, HEADER_H - 1),
-    );
-    gam.draw_rectangle(canvas, header_rect.style(
-        DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 0),
-    )).ok();
-
-    let tb = TextBounds::BoundingBox(Rectangle::new(
-        Point::new(4, 2),
-        Point::new(SCREEN_W - 4, HEADER_H - 2),
-    ));
-    gam.draw_textview(
-        canvas,
-        tv::TextView::new(tb, text)
-            .style(GlyphStyle::Bold)
-            .draw_border(false)
-            .invert(true),
-    ).ok();
+fn draw_header(r: &mut dyn Renderer, text: &str) {
+    r.rect(Rect::new(0, 0, SCREEN_W - 1, HEADER_H - 1), Color::Dark, Color::Dark, 0);
+    r.text(Rect::new(4, 2, SCREEN_W - 4, HEADER_H - 2), text, GlyphStyle::Bold, true);
+}
+
+fn draw_footer(r: &mut dyn Renderer, text: &str) {
+    let y = SCREEN_H - FOOTER_H;
+    r.line(0, y, SCREEN_W - 1, y, Color::Dark, 1);
+    r.text(Rect::new(4, y + 4, SCREEN_W - 4, SCREEN_H - 2), text, GlyphStyle::Small, false);
 }
 
-fn draw_footer(gam: &Gam, canvas: Canvas, text: &str) {
-    let y = 536 - FOOTER_H;
-    gam.draw_line(canvas, Point::new(0, y), Point::new(SCREEN_W - 1, y),
-        DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 1),
-    ).ok();
-
-    let tb = TextBounds::BoundingBox(Rectangle::new(
-        Point::new(4, y + 4),
-        Point::new(SCREEN_W - 4, 536 - 2),
-    ));
-    gam.draw_textview(
-        canvas,
-        tv::TextView::new(tb, text)
-            .style(GlyphStyle::Small)
-            .draw_border(false),
-    ).ok();
+fn draw_text(r: &mut dyn Renderer, x: i16, y: i16, text: &str, style: GlyphStyle) {
+    r.text(Rect::new(x, y, SCREEN_W - 4, y + LINE_H), text, style, false);
 }
 
-fn draw_text(gam: &Gam, canvas: Canvas, x: i16, y: i16, text: &str, style: GlyphStyle) {
-    let tb = TextBounds::BoundingBox(Rectangle::new(
-        Point::new(x, y),
-        Point::new(SCREEN_W - 4, y + LINE_H),
-    ));
-    gam.draw_textview(
-        canvas,
-        tv::TextView::new(tb, text)
-            .style(style)
-            .draw_border(false),
-    ).ok();
+fn draw_text_inverted(r: &mut dyn Renderer, x: i16, y: i16, w: i16, text: &str) {
+    r.rect(Rect::new(x, y, x + w, y + LINE_H), Color::Dark, Color::Dark, 0);
+    r.text(Rect::new(x + 2, y, x + w - 2, y + LINE_H), text, GlyphStyle::Regular, true);
 }
 
-fn draw_text_inverted(gam: &Gam, canvas: Canvas, x: i16, y: i16, w: i16, text: &str) {
-    let bg = Rectangle::new(Point::new(x, y), Point::new(x + w, y + LINE_H));
-    gam.draw_rectangle(canvas, bg.style(
-        DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 0),
-    )).ok();
-
-    let tb = TextBounds::BoundingBox(Rectangle::new(
-        Point::new(x + 2, y),
-        Point::new(x + w - 2, y + LINE_H),
-    ));
-    gam.draw_textview(
-        canvas,
-        tv::TextView::new(tb, text)
-            .style(GlyphStyle::Regular)
-            .draw_border(false)
-            .invert(true),
-    ).ok();
+fn draw_large_text(r: &mut dyn Renderer, x: i16, y: i16, text: &str) {
+    r.text(Rect::new(x, y, SCREEN_W - 4, y + 40), text, GlyphStyle::Large, false);
 }
 
-fn draw_large_text(gam: &Gam, canvas: Canvas, x: i16, y: i16, text: &str) {
-    let tb = TextBounds::BoundingBox(Rectangle::new(
-        Point::new(x, y),
-        Point::new(SCREEN_W - 4, y + 40),
-    ));
-    gam.draw_textview(
-        canvas,
-        tv::TextView::new(tb, text)
-            .style(GlyphStyle::Large)
-            .draw_border(false),
-    ).ok();
+/// Large text word-wrapped to the screen width; returns the height used.
+fn draw_large_wrapped(r: &mut dyn Renderer, x: i16, y: i16, text: &str) -> i16 {
+    let width = ((SCREEN_W - 4 - x) / LARGE_CHAR_W) as usize;
+    let mut line = String::new();
+    let mut h = 0;
+    for word in text.split(' ') {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            draw_large_text(r, x, y + h, &line);
+            h += 40;
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    draw_large_text(r, x, y + h, &line);
+    h + 40
+}
+
+/// Format milliseconds as seconds with one decimal, e.g. "2.3s".
+fn secs(ms: u32) -> String {
+    format!("{}.{}s", ms / 1000, (ms % 1000) / 100)
+}
+
+/// Why scores aren't being kept, if they aren't.
+fn unsaved_warning(app: &MathDrillApp) -> Option<&'static str> {
+    if app.scores_saved() {
+        None
+    } else if app.save_failed {
+        Some("Scores not saved (PDDB write failed)")
+    } else {
+        Some("Scores not saved (PDDB not mounted)")
+    }
 }
 
-pub fn draw(app: &MathDrillApp, gam: &Gam, canvas: Canvas) {
-    gam.draw_rectangle(
-        canvas,
-        Rectangle::new(Point::new(0, 0), Point::new(SCREEN_W - 1, 535))
-            .style(DrawStyle::new(PixelColor::Light, PixelColor::Light, 0)),
-    ).ok();
+pub fn draw(app: &MathDrillApp, r: &mut dyn Renderer) {
+    r.rect(Rect::new(0, 0, SCREEN_W - 1, SCREEN_H - 1), Color::Light, Color::Light, 0);
 
     match app.state {
-        AppState::Menu => draw_menu(app, gam, canvas),
-        AppState::Playing => draw_playing(app, gam, canvas),
-        AppState::Feedback => draw_feedback(app, gam, canvas),
-        AppState::Results => draw_results(app, gam, canvas),
-        AppState::BestScores => draw_best_scores(app, gam, canvas),
+        AppState::ResumePrompt => draw_resume_prompt(app, r),
+        AppState::ConfirmDiscard => {
+            draw_resume_prompt(app, r);
+            let answered = app.saved_session.as_ref().map_or(0, |saved| saved.session_problems.len());
+            draw_confirm(r, "Discard session?", answered);
+        }
+        AppState::Menu => draw_menu(app, r),
+        AppState::Playing => draw_playing(app, r),
+        AppState::Feedback => draw_feedback(app, r),
+        AppState::Paused => draw_paused(app, r),
+        AppState::ConfirmAbandon => {
+            draw_paused(app, r);
+            draw_confirm(r, "Abandon session?", app.session_problems.len());
+        }
+        AppState::Results => draw_results(app, r),
+        AppState::BestScores => draw_best_scores(app, r),
+        AppState::Heatmap => draw_heatmap(app, r),
+    }
+
+    r.flush();
+}
+
+fn draw_resume_prompt(app: &MathDrillApp, r: &mut dyn Renderer) {
+    draw_header(r, "Math Drill");
+
+    let mut y = HEADER_H + 60;
+    draw_large_text(r, 30, y, "Resume session?");
+    y += 50;
+
+    if let Some(saved) = &app.saved_session {
+        let kind = format!(
+            "{}  {}  {}",
+            saved.session_kind.label(),
+            saved.op_mode.label(),
+            saved.difficulty.label()
+        );
+        draw_text(r, 30, y, &kind, GlyphStyle::Regular);
+        y += LINE_H;
+        let progress = if saved.retry_round > 0 {
+            format!("Retry round {}", saved.retry_round)
+        } else {
+            format!("{} answered, {} correct", saved.session_problems.len(), saved.correct_count)
+        };
+        draw_text(r, 30, y, &progress, GlyphStyle::Regular);
     }
 
-    gam.redraw().ok();
+    draw_footer(r, "Enter=Resume  Menu=Discard");
 }
 
-fn draw_menu(app: &MathDrillApp, gam: &Gam, canvas: Canvas) {
-    draw_header(gam, canvas, "Math Drill");
+fn draw_menu(app: &MathDrillApp, r: &mut dyn Renderer) {
+    draw_header(r, "Math Drill");
 
     let mut y = HEADER_H + 20;
 
     // ASCII art title
-    draw_text(gam, canvas, 40, y, "Quick arithmetic training", GlyphStyle::Regular);
-    y += LINE_H + 20;
+    draw_text(r, 40, y, "Quick arithmetic training", GlyphStyle::Regular);
+    y += LINE_H;
+    if let Some(warning) = unsaved_warning(app) {
+        draw_text(r, 40, y, warning, GlyphStyle::Small);
+    }
+    y += 20;
 
     // Operation selector
     let op_label = format!("Operation: < {} >", app.op_mode.label());
     if app.menu_field == MenuField::Operation {
-        draw_text_inverted(gam, canvas, 20, y, SCREEN_W - 40, &op_label);
+        draw_text_inverted(r, 20, y, SCREEN_W - 40, &op_label);
     } else {
-        draw_text(gam, canvas, 24, y, &op_label, GlyphStyle::Regular);
+        draw_text(r, 24, y, &op_label, GlyphStyle::Regular);
     }
     y += LINE_H + 10;
 
     // Difficulty selector
     let diff_label = format!("Difficulty: < {} >", app.difficulty.label());
     if app.menu_field == MenuField::Difficulty {
-        draw_text_inverted(gam, canvas, 20, y, SCREEN_W - 40, &diff_label);
+        draw_text_inverted(r, 20, y, SCREEN_W - 40, &diff_label);
+    } else {
+        draw_text(r, 24, y, &diff_label, GlyphStyle::Regular);
+    }
+    y += LINE_H + 10;
+
+    // What to solve for
+    let find_label = match (app.op_mode.allows_blank(), app.find_operand) {
+        (false, _) => format!("Find: not for {}", app.op_mode.label()),
+        (true, true) => String::from("Find: < Missing operand >"),
+        (true, false) => String::from("Find: < Answer >"),
+    };
+    if app.menu_field == MenuField::Find {
+        draw_text_inverted(r, 20, y, SCREEN_W - 40, &find_label);
     } else {
-        draw_text(gam, canvas, 24, y, &diff_label, GlyphStyle::Regular);
+        draw_text(r, 24, y, &find_label, GlyphStyle::Regular);
+    }
+    y += LINE_H + 10;
+
+    // Signed operands for the four operations
+    let negatives_label = match (app.op_mode.allows_negatives(), app.negatives) {
+        (false, _) => format!("Negatives: not for {}", app.op_mode.label()),
+        (true, true) => String::from("Negatives: < On >"),
+        (true, false) => String::from("Negatives: < Off >"),
+    };
+    if app.menu_field == MenuField::Negatives {
+        draw_text_inverted(r, 20, y, SCREEN_W - 40, &negatives_label);
+    } else {
+        draw_text(r, 24, y, &negatives_label, GlyphStyle::Regular);
+    }
+    y += LINE_H + 10;
+
+    // Mode selector
+    let mode_label = format!("Mode: < {} >", app.session_kind.label());
+    if app.menu_field == MenuField::Mode {
+        draw_text_inverted(r, 20, y, SCREEN_W - 40, &mode_label);
+    } else {
+        draw_text(r, 24, y, &mode_label, GlyphStyle::Regular);
+    }
+    y += LINE_H + 10;
+
+    // Session length selector
+    let len_label = match app.session_kind {
+        SessionKind::Standard => format!("Length: < {} problems >", app.session_length),
+        SessionKind::Sprint(secs) => format!("Length: < {} seconds >", secs),
+        SessionKind::Survival => format!("Length: until {} lives are lost", SURVIVAL_LIVES),
+        SessionKind::Zen => String::from("Length: until you press Menu"),
+    };
+    if app.menu_field == MenuField::Length {
+        draw_text_inverted(r, 20, y, SCREEN_W - 40, &len_label);
+    } else {
+        draw_text(r, 24, y, &len_label, GlyphStyle::Regular);
+    }
+    y += LINE_H + 10;
+
+    // Per-problem time limit for the selected difficulty
+    let limit_label = match (app.difficulty_limit_secs(), app.session_kind) {
+        (_, SessionKind::Zen) => String::from("Time limit: none in Zen"),
+        (0, SessionKind::Survival) => {
+            format!("Time limit: < Off ({}s in Survival) >", SURVIVAL_PROBLEM_SECS)
+        }
+        (0, _) => String::from("Time limit: < Off >"),
+        (secs, _) => format!("Time limit: < {}s per problem >", secs),
+    };
+    if app.menu_field == MenuField::TimeLimit {
+        draw_text_inverted(r, 20, y, SCREEN_W - 40, &limit_label);
+    } else {
+        draw_text(r, 24, y, &limit_label, GlyphStyle::Regular);
     }
     y += LINE_H + 20;
 
     // Start button
     let start_label = ">>> START QUIZ <<<";
     if app.menu_field == MenuField::Start {
-        draw_text_inverted(gam, canvas, 60, y, SCREEN_W - 120, start_label);
+        draw_text_inverted(r, 60, y, SCREEN_W - 120, start_label);
     } else {
-        draw_text(gam, canvas, 80, y, start_label, GlyphStyle::Regular);
+        draw_text(r, 80, y, start_label, GlyphStyle::Regular);
+    }
+    y += LINE_H + 10;
+
+    // Heatmap link
+    let heatmap_label = "Fact Heatmap";
+    if app.menu_field == MenuField::Heatmap {
+        draw_text_inverted(r, 60, y, SCREEN_W - 120, heatmap_label);
+    } else {
+        draw_text(r, 80, y, heatmap_label, GlyphStyle::Regular);
     }
     y += LINE_H + 10;
 
     // Best scores link
     let best_label = "View Best Scores";
     if app.menu_field == MenuField::BestScores {
-        draw_text_inverted(gam, canvas, 60, y, SCREEN_W - 120, best_label);
+        draw_text_inverted(r, 60, y, SCREEN_W - 120, best_label);
     } else {
-        draw_text(gam, canvas, 80, y, best_label, GlyphStyle::Regular);
+        draw_text(r, 80, y, best_label, GlyphStyle::Regular);
     }
 
-    draw_footer(gam, canvas, "Up/Down=Select  </>=Cycle  Enter=Go  Menu=Quit");
+    draw_footer(r, "Up/Down=Select  </>=Cycle  Enter=Go  Menu=Quit");
 }
 
-fn draw_playing(app: &MathDrillApp, gam: &Gam, canvas: Canvas) {
-    let header = format!(
-        "Problem {}/10  Streak: {}  Score: {}/{}",
-        app.problem_num + 1,
-        app.streak,
-        app.correct_count,
-        app.problem_num
-    );
-    draw_header(gam, canvas, &header);
+fn draw_playing(app: &MathDrillApp, r: &mut dyn Renderer) {
+    let (header, progress) = match app.session_kind {
+        // Retrying missed problems: this pass's progress, not the session's
+        _ if app.retry_round > 0 => (
+            format!("Retry round {}  {} left", app.retry_round, app.retry_queue.len() + 1),
+            None,
+        ),
+        SessionKind::Standard => (
+            format!(
+                "Problem {}/{}  Streak: {}  Score: {}/{}",
+                app.problem_num + 1,
+                app.session_length,
+                app.streak,
+                app.correct_count,
+                app.problem_num
+            ),
+            Some((app.problem_num as i32, app.session_length as i32)),
+        ),
+        SessionKind::Sprint(secs) => {
+            // Round up so the countdown reads 0:00 only once time is out
+            let left = app.sprint_remaining_ms().div_ceil(1000) as u32;
+            (
+                format!(
+                    "Sprint {}:{:02}  Streak: {}  Score: {}/{}",
+                    left / 60,
+                    left % 60,
+                    app.streak,
+                    app.correct_count,
+                    app.problem_num
+                ),
+                Some(((secs - left) as i32, secs as i32)),
+            )
+        }
+        SessionKind::Survival => (
+            format!(
+                "Survival  Level {}  Streak: {}  Score: {}/{}",
+                app.survival_level() + 1,
+                app.streak,
+                app.correct_count,
+                app.problem_num
+            ),
+            None,
+        ),
+        // No clock or score on screen: just the problems
+        SessionKind::Zen => (String::from("Zen"), None),
+    };
+    draw_header(r, &header);
 
-    // Progress bar
+    // Progress bar (problems done, or time used in a Sprint)
     let bar_y = HEADER_H + 4;
     let bar_h = 8;
-    let filled_w = ((app.problem_num as i16) * (SCREEN_W - 20)) / 10;
-    let bar_bg = Rectangle::new(
-        Point::new(10, bar_y),
-        Point::new(SCREEN_W - 10, bar_y + bar_h),
-    );
-    gam.draw_rectangle(canvas, bar_bg.style(
-        DrawStyle::new(PixelColor::Light, PixelColor::Dark, 1),
-    )).ok();
-    if filled_w > 0 {
-        let bar_fill = Rectangle::new(
-            Point::new(10, bar_y),
-            Point::new(10 + filled_w, bar_y + bar_h),
-        );
-        gam.draw_rectangle(canvas, bar_fill.style(
-            DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 0),
-        )).ok();
+    if let Some((done, out_of)) = progress {
+        let filled_w = (done * (SCREEN_W - 20) as i32 / out_of) as i16;
+        r.rect(Rect::new(10, bar_y, SCREEN_W - 10, bar_y + bar_h), Color::Light, Color::Dark, 1);
+        if filled_w > 0 {
+            r.rect(Rect::new(10, bar_y, 10 + filled_w, bar_y + bar_h), Color::Dark, Color::Dark, 0);
+        }
+    } else if app.session_kind == SessionKind::Survival && app.retry_round == 0 {
+        draw_lives(app, r, bar_y);
     }
 
     // Problem display — large and centered
     if let Some(ref problem) = app.current_problem {
-        let problem_text = problem.display();
         let y_problem = HEADER_H + 80;
-        draw_large_text(gam, canvas, 30, y_problem, &problem_text);
+        let problem_h = match &problem.kind {
+            ProblemKind::Fraction(fraction) => {
+                draw_fraction_problem(r, 30, y_problem, fraction);
+                40
+            }
+            ProblemKind::Percent(_) => draw_large_wrapped(r, 30, y_problem, &problem.display()),
+            ProblemKind::Expression { .. } => {
+                // A long expression wraps, with "= ?" on a line of its own below
+                let h = draw_large_wrapped(r, 30, y_problem, &problem.question());
+                draw_large_text(r, 30, y_problem + h, "= ?");
+                h + 40
+            }
+            ProblemKind::Arithmetic { .. } | ProblemKind::Decimal(_) => {
+                draw_large_text(r, 30, y_problem, &problem.display());
+                40
+            }
+        };
+
+        // Time left, as a bar that shrinks to nothing at the limit
+        if let (Some(limit), Some(left)) = (app.problem_limit_ms(), app.problem_remaining_ms()) {
+            let y_bar = y_problem + problem_h + 12;
+            let w = (left * (SCREEN_W - 60) as u64 / limit) as i16;
+            r.rect(Rect::new(30, y_bar, SCREEN_W - 30, y_bar + 6), Color::Light, Color::Dark, 1);
+            if w > 0 {
+                r.rect(Rect::new(30, y_bar, 30 + w, y_bar + 6), Color::Dark, Color::Dark, 0);
+            }
+        }
 
         // Answer input
-        let y_answer = y_problem + 80;
+        let y_answer = y_problem + problem_h + 40;
         let answer_display = if app.answer_buffer.is_empty() {
             String::from("Type your answer: _")
         } else {
             format!("Your answer: {}_", app.answer_buffer)
         };
-        draw_text(gam, canvas, 30, y_answer, &answer_display, GlyphStyle::Regular);
+        draw_text(r, 30, y_answer, &answer_display, GlyphStyle::Regular);
 
         // Hint for negative
         let y_hint = y_answer + LINE_H + 10;
-        draw_text(gam, canvas, 30, y_hint, "0-9, -, Backspace, Enter", GlyphStyle::Small);
+        let hint = match problem.kind {
+            ProblemKind::Fraction(_) => "0-9, / for a/b, Space for 1 1/2, Backspace, Enter",
+            ProblemKind::Decimal(_) | ProblemKind::Percent(_) => "0-9, ., Backspace, Enter",
+            ProblemKind::Arithmetic { .. } | ProblemKind::Expression { .. } => "0-9, -, Backspace, Enter",
+        };
+        draw_text(r, 30, y_hint, hint, GlyphStyle::Small);
     }
 
-    draw_footer(gam, canvas, "Type answer + Enter  Menu=Quit session");
+    draw_footer(r, "Type answer + Enter  Menu=Pause");
 }
 
-fn draw_feedback(app: &MathDrillApp, gam: &Gam, canvas: Canvas) {
+/// `a op b = ?` with each fraction stacked over its bar, mixed numbers
+/// with the whole part at full size.
+fn draw_fraction_problem(r: &mut dyn Renderer, x: i16, y: i16, problem: &FractionProblem) {
+    let mut x = x;
+    let large = |r: &mut dyn Renderer, x: i16, text: &str| {
+        draw_large_text(r, x, y, text);
+        x + text.chars().count() as i16 * LARGE_CHAR_W + 8
+    };
+    let operand = |r: &mut dyn Renderer, x: i16, f: &Fraction| {
+        let (whole, rest) = f.split();
+        let mut x = x;
+        if whole != 0 {
+            x = large(r, x, &format!("{}", whole)) - 4;
+        }
+        if rest.num != 0 {
+            let (num, den) = (format!("{}", rest.num), format!("{}", rest.den));
+            let w = num.len().max(den.len()) as i16 * BOLD_CHAR_W + 4;
+            r.text(Rect::new(x, y - 4, x + w, y + 16), &num, GlyphStyle::Bold, false);
+            r.line(x, y + 18, x + w, y + 18, Color::Dark, 2);
+            r.text(Rect::new(x, y + 20, x + w, y + 40), &den, GlyphStyle::Bold, false);
+            x += w + 8;
+        }
+        x
+    };
+    x = operand(r, x, &problem.a);
+    x = large(r, x, fraction::symbol(problem.operation));
+    x = operand(r, x, &problem.b);
+    large(r, x, "= ?");
+}
+
+/// Survival lives as a row of boxes, filled for each one left.
+fn draw_lives(app: &MathDrillApp, r: &mut dyn Renderer, y: i16) {
+    draw_text(r, 10, y - 2, "Lives:", GlyphStyle::Small);
+    for i in 0..SURVIVAL_LIVES {
+        let x = 60 + i as i16 * 18;
+        let fill = if i < app.lives { Color::Dark } else { Color::Light };
+        r.rect(Rect::new(x, y, x + 12, y + 12), fill, Color::Dark, 1);
+    }
+}
+
+fn draw_feedback(app: &MathDrillApp, r: &mut dyn Renderer) {
+    let timed_out = app.feedback_timed_out;
+    let zen = app.session_kind == SessionKind::Zen;
     if app.feedback_correct {
-        draw_header(gam, canvas, "CORRECT!");
+        draw_header(r, "CORRECT!");
+    } else if zen {
+        draw_header(r, "TRY AGAIN");
+    } else if timed_out {
+        draw_header(r, "TIME'S UP");
     } else {
-        draw_header(gam, canvas, "WRONG");
+        draw_header(r, "WRONG");
     }
 
     let y = HEADER_H + 60;
 
     if let Some(ref problem) = app.feedback_problem {
         if app.feedback_correct {
-            draw_large_text(gam, canvas, 30, y, &problem.display_with_answer());
+            draw_large_text(r, 30, y, &problem.display_with_answer());
             let y2 = y + 50;
-            let streak_msg = format!("Streak: {}", app.streak);
-            draw_text(gam, canvas, 30, y2, &streak_msg, GlyphStyle::Regular);
+            let streak_msg = if zen {
+                String::from("Well done!")
+            } else if app.retry_round > 0 {
+                String::from("Got it this time")
+            } else {
+                format!("Streak: {}", app.streak)
+            };
+            draw_text(r, 30, y2, &streak_msg, GlyphStyle::Regular);
+        } else if zen {
+            // Keep the answer hidden: the same problem comes straight back
+            let wrong = format!("You said: {}", app.feedback_user_input);
+            draw_text(r, 30, y, &wrong, GlyphStyle::Regular);
+            draw_large_text(r, 30, y + LINE_H + 10, &problem.display());
         } else {
-            let wrong = format!("You said: {}", app.feedback_user_answer);
-            draw_text(gam, canvas, 30, y, &wrong, GlyphStyle::Regular);
+            let wrong = if timed_out {
+                String::from("No answer in time")
+            } else {
+                format!("You said: {}", app.feedback_user_input)
+            };
+            draw_text(r, 30, y, &wrong, GlyphStyle::Regular);
             let y2 = y + LINE_H + 10;
             let correct = format!("Correct: {}", problem.display_with_answer());
-            draw_large_text(gam, canvas, 30, y2, &correct);
+            draw_large_text(r, 30, y2, &correct);
+            if app.session_kind == SessionKind::Survival && app.retry_round == 0 {
+                let lives = format!("Lives left: {}", app.lives);
+                draw_text(r, 30, y2 + 50, &lives, GlyphStyle::Regular);
+            }
+        }
+    }
+
+    draw_footer(r, "Enter=Next  (auto-advances in 1.5s)");
+}
+
+fn draw_paused(app: &MathDrillApp, r: &mut dyn Renderer) {
+    draw_header(r, "Paused");
+
+    let mut y = HEADER_H + 60;
+    draw_large_text(r, 30, y, "Paused");
+    y += 50;
+    // No problem on screen, so the pause can't be used to work it out
+    draw_text(r, 30, y, "The problem is hidden until you resume.", GlyphStyle::Small);
+    y += LINE_H;
+
+    // Feedback has already counted the problem it shows
+    let shown = match app.paused_from {
+        AppState::Playing => app.problem_num + 1,
+        _ => app.problem_num,
+    };
+    let progress = match app.session_kind {
+        _ if app.retry_round > 0 => Some(format!("Retry round {}", app.retry_round)),
+        SessionKind::Standard => Some(format!(
+            "Problem {}/{}  Score: {}/{}",
+            shown,
+            app.session_length,
+            app.correct_count,
+            app.problem_num
+        )),
+        SessionKind::Sprint(_) => {
+            let left = app.sprint_end_ms.saturating_sub(app.paused_at).div_ceil(1000);
+            Some(format!("Sprint: {}:{:02} left", left / 60, left % 60))
         }
+        SessionKind::Survival => Some(format!("Survival: {} lives left", app.lives)),
+        SessionKind::Zen => None,
+    };
+    if let Some(progress) = progress {
+        draw_text(r, 30, y, &progress, GlyphStyle::Regular);
     }
 
-    draw_footer(gam, canvas, "Enter=Next  (auto-advances in 1.5s)");
+    let leave = if app.retry_round > 0 {
+        "Enter=Resume  Menu=Back to results"
+    } else if app.session_kind == SessionKind::Zen {
+        "Enter=Resume  Menu=Finish"
+    } else {
+        "Enter=Resume  Menu=Quit session"
+    };
+    draw_footer(r, leave);
+}
+
+/// Dialog over the pause screen or resume prompt before a session with
+/// `answered` attempts is thrown away.
+fn draw_confirm(r: &mut dyn Renderer, title: &str, answered: usize) {
+    let top = 180;
+    r.rect(Rect::new(16, top, SCREEN_W - 17, top + 120), Color::Light, Color::Dark, 2);
+
+    let mut y = top + 12;
+    draw_text(r, 30, y, title, GlyphStyle::Bold);
+    y += LINE_H + 4;
+    let logged = if answered == 0 {
+        String::from("Nothing answered yet.")
+    } else {
+        format!("{} answered, logged as incomplete.", answered)
+    };
+    draw_text(r, 30, y, &logged, GlyphStyle::Small);
+    y += LINE_H + 12;
+    draw_text(r, 30, y, "Enter=Yes, Menu=No", GlyphStyle::Regular);
 }
 
-fn draw_results(app: &MathDrillApp, gam: &Gam, canvas: Canvas) {
+fn draw_results(app: &MathDrillApp, r: &mut dyn Renderer) {
+    let mut y = if app.session_kind == SessionKind::Zen {
+        draw_zen_summary(app, r)
+    } else {
+        draw_score_summary(app, r)
+    };
+
+    // Show each problem result
+    draw_text(r, 8, y, "Review (time, 1st keystroke, backspaces):", GlyphStyle::Small);
+    y += 16;
+
+    for attempt in app.session_problems.iter() {
+        let mark = if attempt.correct { "+" } else { "X" };
+        let mut line = if attempt.correct {
+            format!("{} {}", mark, attempt.problem.display_with_answer())
+        } else if attempt.timed_out {
+            format!("{} {} (timed out)", mark, attempt.problem.display_with_answer())
+        } else {
+            format!("{} {} (you: {})", mark, attempt.problem.display_with_answer(), attempt.given())
+        };
+        line.push_str(&format!(
+            "  {}, 1st {}",
+            secs(attempt.time_ms),
+            secs(attempt.first_key_ms)
+        ));
+        if attempt.backspaces > 0 {
+            line.push_str(&format!(", {}bs", attempt.backspaces));
+        }
+        draw_text(r, 8, y, &line, GlyphStyle::Small);
+        y += 16;
+        if y > SCREEN_H - FOOTER_H - 16 {
+            break;
+        }
+    }
+
+    if app.can_retry() {
+        draw_footer(r, "Enter=Menu  R=Retry missed");
+    } else {
+        draw_footer(r, "Enter=Menu");
+    }
+}
+
+/// Header and score lines above the review list; returns where it ends.
+fn draw_score_summary(app: &MathDrillApp, r: &mut dyn Renderer) -> i16 {
     let pct = if !app.session_problems.is_empty() {
-        (app.correct_count as u32 * 100) / app.session_problems.len() as u32
+        (app.correct_count * 100) / app.session_problems.len() as u32
     } else {
         0
     };
     let header = format!("Results — {}%", pct);
-    draw_header(gam, canvas, &header);
+    draw_header(r, &header);
 
     let mut y = HEADER_H + 10;
 
@@ -266,102 +592,481 @@ fn draw_results(app: &MathDrillApp, gam: &Gam, canvas: Canvas) {
         app.session_problems.len(),
         app.best_streak
     );
-    draw_text(gam, canvas, 8, y, &score_line, GlyphStyle::Regular);
+    draw_text(r, 8, y, &score_line, GlyphStyle::Regular);
     y += LINE_H + 4;
 
     let avg_line = format!("Avg time: {}ms per problem", app.avg_time_ms());
-    draw_text(gam, canvas, 8, y, &avg_line, GlyphStyle::Regular);
-    y += LINE_H + 10;
+    draw_text(r, 8, y, &avg_line, GlyphStyle::Regular);
+    y += LINE_H + 4;
 
-    // Show each problem result
-    draw_text(gam, canvas, 8, y, "Review:", GlyphStyle::Small);
-    y += 16;
+    if let SessionKind::Sprint(secs) = app.session_kind {
+        let pace = app.pace_per_min_x10();
+        let pace_line = format!("Pace: {}.{} problems/min over {}s", pace / 10, pace % 10, secs);
+        draw_text(r, 8, y, &pace_line, GlyphStyle::Regular);
+        y += LINE_H + 4;
+    }
+    if app.session_kind == SessionKind::Survival {
+        let run_line = format!("Run ended at level {}", app.survival_level() + 1);
+        draw_text(r, 8, y, &run_line, GlyphStyle::Regular);
+        y += LINE_H + 4;
+    }
+    y += 6;
 
-    for (i, (problem, user_ans, correct)) in app.session_problems.iter().enumerate() {
-        let mark = if *correct { "+" } else { "X" };
-        let line = if *correct {
-            format!("{} {} {}", mark, problem.display_with_answer(), "")
-        } else {
-            format!("{} {} (you: {})", mark, problem.display_with_answer(), user_ans)
-        };
-        draw_text(gam, canvas, 8, y, &line, GlyphStyle::Small);
+    if let Some(rounds) = app.retry_cleared {
+        let cleared = format!(
+            "Missed problems fixed in {} retry round{}",
+            rounds,
+            if rounds == 1 { "" } else { "s" }
+        );
+        draw_text(r, 8, y, &cleared, GlyphStyle::Regular);
+        y += LINE_H + 4;
+    } else if app.can_retry() {
+        let missed = app.session_problems.iter().filter(|a| !a.correct).count();
+        let offer = format!("Press R to retry the {} missed", missed);
+        draw_text(r, 8, y, &offer, GlyphStyle::Regular);
+        y += LINE_H + 4;
+    }
+
+    if let Some(warning) = unsaved_warning(app) {
+        draw_text(r, 8, y, warning, GlyphStyle::Small);
         y += 16;
-        if y > 536 - FOOTER_H - 16 {
-            break;
-        }
     }
+    y
+}
 
-    draw_footer(gam, canvas, "Enter=Menu");
+/// Zen is unscored: just how many problems were worked through.
+fn draw_zen_summary(app: &MathDrillApp, r: &mut dyn Renderer) -> i16 {
+    draw_header(r, "Zen session");
+    let y = HEADER_H + 10;
+    let done = format!("Problems done: {}", app.correct_count);
+    draw_text(r, 8, y, &done, GlyphStyle::Regular);
+    y + LINE_H + 10
 }
 
-fn draw_best_scores(app: &MathDrillApp, gam: &Gam, canvas: Canvas) {
-    draw_header(gam, canvas, "Best Scores");
+fn draw_best_scores(app: &MathDrillApp, r: &mut dyn Renderer) {
+    let header = match app.session_kind {
+        SessionKind::Standard => format!("Best Scores ({} problems)", app.session_length),
+        SessionKind::Sprint(secs) => format!("Best Scores (Sprint {}s)", secs),
+        SessionKind::Survival => String::from("Best Scores (Survival)"),
+        SessionKind::Zen => String::from("Best Scores (Zen)"),
+    };
+    draw_header(r, &header);
 
     let mut y = HEADER_H + 10;
 
-    // Clone app to avoid borrow issues — we need mutable access for storage
-    // but only have immutable. Work around by just showing what we can.
-    for diff in Difficulty::all() {
-        draw_text(gam, canvas, 8, y, diff.label(), GlyphStyle::Bold);
-        y += LINE_H + 2;
+    if app.session_kind == SessionKind::Zen {
+        draw_text(r, 8, y, "Zen sessions are not scored", GlyphStyle::Regular);
+        y += LINE_H + 8;
+    } else {
+        for (diff, stats) in Difficulty::all().iter().zip(app.best_scores.iter()) {
+            draw_text(r, 8, y, diff.name(), GlyphStyle::Bold);
+            y += LINE_H;
 
-        // We can't call get_best from here since we only have &app
-        // The scores will be loaded in a dedicated data struct
-        draw_text(gam, canvas, 16, y, "(Select to see stats)", GlyphStyle::Small);
-        y += 20;
+            match stats {
+                Some(s) => {
+                    let line1 = match app.session_kind {
+                        SessionKind::Survival => {
+                            format!("  Longest run: {} correct  Streak: {}", s.correct, s.streak)
+                        }
+                        _ => format!("  Score: {}/{}  Streak: {}", s.correct, s.total, s.streak),
+                    };
+                    draw_text(r, 8, y, &line1, GlyphStyle::Regular);
+                    y += LINE_H;
+                    let line2 = match app.session_kind {
+                        SessionKind::Sprint(secs) => {
+                            let pace = s.total * 600 / secs.max(1);
+                            format!("  Avg: {}ms/problem, {}.{}/min", s.avg_ms, pace / 10, pace % 10)
+                        }
+                        _ => format!("  Avg: {}ms/problem", s.avg_ms),
+                    };
+                    draw_text(r, 8, y, &line2, GlyphStyle::Small);
+                    y += 18;
+                }
+                None => {
+                    draw_text(r, 16, y, "  No scores yet", GlyphStyle::Small);
+                    y += 18;
+                }
+            }
+            y += 8;
+        }
     }
 
-    // Note: In practice, best scores are cached in the app struct
-    // before entering this state. This is a simplified display.
+    let logged = format!("Sessions logged: {}", app.sessions_logged);
+    draw_text(r, 8, y, &logged, GlyphStyle::Small);
 
-    draw_footer(gam, canvas, "Enter=Back  Menu=Back");
+    draw_footer(r, "Enter=Back  Menu=Back");
 }
 
-/// Draw best scores with pre-loaded data.
-pub fn draw_best_with_data(
-    gam: &Gam,
-    canvas: Canvas,
-    easy: Option<&crate::storage::BestStats>,
-    medium: Option<&crate::storage::BestStats>,
-    hard: Option<&crate::storage::BestStats>,
-) {
-    gam.draw_rectangle(
-        canvas,
-        Rectangle::new(Point::new(0, 0), Point::new(SCREEN_W - 1, 535))
-            .style(DrawStyle::new(PixelColor::Light, PixelColor::Light, 0)),
-    ).ok();
-
-    draw_header(gam, canvas, "Best Scores");
+fn draw_heatmap(app: &MathDrillApp, r: &mut dyn Renderer) {
+    const CELL: i16 = 24;
+    const GRID_X: i16 = 36;
+    const GRID_Y: i16 = HEADER_H + 28;
 
-    let mut y = HEADER_H + 10;
+    let header = format!("Heatmap: {}", app.heatmap_op.label());
+    draw_header(r, &header);
+
+    // Column labels across the top, row labels down the side
+    for i in 1..=GRID_MAX {
+        let offset = (i as i16 - 1) * CELL;
+        let label = format!("{}", i);
+        r.text(Rect::new(GRID_X + offset + 3, GRID_Y - 20, GRID_X + offset + CELL, GRID_Y - 2),
+            &label, GlyphStyle::Small, false);
+        r.text(Rect::new(8, GRID_Y + offset + 3, GRID_X - 2, GRID_Y + offset + CELL),
+            &label, GlyphStyle::Small, false);
+    }
 
-    let entries: [(&str, Option<&crate::storage::BestStats>); 3] = [
-        ("Easy", easy),
-        ("Medium", medium),
-        ("Hard", hard),
-    ];
+    for row in 1..=GRID_MAX {
+        for col in 1..=GRID_MAX {
+            let x0 = GRID_X + (col as i16 - 1) * CELL;
+            let y0 = GRID_Y + (row as i16 - 1) * CELL;
+            let cell = Rect::new(x0, y0, x0 + CELL - 1, y0 + CELL - 1);
+            let selected = row == app.heatmap_row && col == app.heatmap_col;
+            let border = if selected { 3 } else { 1 };
 
-    for (label, stats) in entries.iter() {
-        draw_text(gam, canvas, 8, y, label, GlyphStyle::Bold);
-        y += LINE_H;
+            let key = FactKey::grid(app.heatmap_op, row, col);
+            match app.facts.get(&key) {
+                None => r.rect(cell, Color::Light, Color::Dark, border),
+                Some(stats) => {
+                    let pct = stats.accuracy_pct();
+                    if pct < 60 {
+                        r.rect(cell, Color::Dark, Color::Dark, border);
+                    } else {
+                        r.rect(cell, Color::Light, Color::Dark, border);
+                        if pct < 90 {
+                            r.rect(Rect::new(x0 + 6, y0 + 6, x0 + CELL - 7, y0 + CELL - 7),
+                                Color::Dark, Color::Dark, 0);
+                        }
+                    }
+                    if stats.median_ms() > SLOW_MS {
+                        let ink = if pct < 60 { Color::Light } else { Color::Dark };
+                        r.line(x0 + 3, y0 + CELL - 4, x0 + CELL - 4, y0 + 3, ink, 2);
+                    }
+                }
+            }
+        }
+    }
+
+    // Details for the selected fact
+    let mut y = GRID_Y + GRID_MAX as i16 * CELL + 8;
+    let key = FactKey::grid(app.heatmap_op, app.heatmap_row, app.heatmap_col);
+    let fact = key.problem().display_with_answer();
+    let detail = match app.facts.get(&key) {
+        Some(stats) => format!(
+            "{}: {}/{} correct ({}%), median {}",
+            fact,
+            stats.correct,
+            stats.attempts,
+            stats.accuracy_pct(),
+            secs(stats.median_ms())
+        ),
+        None => format!("{}: not practised yet", fact),
+    };
+    draw_text(r, 8, y, &detail, GlyphStyle::Regular);
+    y += LINE_H + 6;
+
+    draw_text(r, 8, y, "Solid <60%  Dot 60-89%  Empty 90%+", GlyphStyle::Small);
+    y += 16;
+    draw_text(r, 8, y, "Slash = median over 4s", GlyphStyle::Small);
+
+    draw_footer(r, "Arrows=Move  Enter=Next operation  Menu=Back");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Driver;
+    use crate::render::TextRenderer;
 
-        match stats {
-            Some(s) => {
-                let line1 = format!("  Score: {}/{}  Streak: {}", s.correct, s.total, s.streak);
-                draw_text(gam, canvas, 8, y, &line1, GlyphStyle::Regular);
-                y += LINE_H;
-                let line2 = format!("  Avg: {}ms/problem", s.avg_ms);
-                draw_text(gam, canvas, 8, y, &line2, GlyphStyle::Small);
-                y += 18;
+    /// Compare a screen against `snapshots/{name}.txt`. Run with
+    /// `UPDATE_SNAPSHOTS=1` to rewrite the golden files after a UI change.
+    fn assert_snapshot(app: &MathDrillApp, name: &str) {
+        let mut r = TextRenderer::new();
+        draw(app, &mut r);
+        let actual = r.snapshot();
+        let path = format!("{}/snapshots/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, &actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("missing snapshot {}", path));
+        assert_eq!(actual, expected, "screen {} differs from its snapshot", name);
+    }
+
+    #[test]
+    fn menu_screen() {
+        let d = Driver::new(1);
+        assert_snapshot(&d.app, "menu");
+    }
+
+    #[test]
+    fn playing_screen() {
+        let mut d = Driver::new(1);
+        d.start();
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        d.keys("12");
+        assert_snapshot(&d.app, "playing");
+    }
+
+    #[test]
+    fn time_limit_bar() {
+        let mut d = Driver::new(1);
+        d.app.problem_limit_secs = [10, 0, 0];
+        d.start();
+        d.advance(2500);
+        assert_snapshot(&d.app, "playing_time_limit");
+    }
+
+    #[test]
+    fn paused_screen() {
+        let mut d = Driver::new(1);
+        d.start();
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        d.keys("1");
+        d.press(KEY_MENU);
+        assert_snapshot(&d.app, "paused");
+    }
+
+    #[test]
+    fn paused_from_feedback_screen() {
+        let mut d = Driver::new(1);
+        d.start();
+        for _ in 0..9 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        d.answer_correctly();
+        d.press(KEY_MENU);
+        assert_snapshot(&d.app, "paused_feedback");
+    }
+
+    #[test]
+    fn decimal_playing_screen() {
+        let mut d = Driver::new(2);
+        d.app.op_mode = OpMode::Decimals;
+        d.app.difficulty = crate::problems::Difficulty::Medium;
+        d.start();
+        d.keys("1.");
+        assert_snapshot(&d.app, "decimal_playing");
+    }
+
+    #[test]
+    fn percent_playing_screen() {
+        let mut d = Driver::new(5);
+        d.app.op_mode = OpMode::Single(crate::problems::Operation::Percent);
+        d.app.difficulty = crate::problems::Difficulty::Hard;
+        d.start();
+        d.keys("2.");
+        assert_snapshot(&d.app, "percent_playing");
+    }
+
+    #[test]
+    fn power_playing_screen() {
+        let mut d = Driver::new(10);
+        d.app.op_mode = OpMode::Single(crate::problems::Operation::Power);
+        d.start();
+        d.keys("6");
+        assert_snapshot(&d.app, "power_playing");
+    }
+
+    #[test]
+    fn fraction_playing_screen() {
+        let mut d = Driver::new(15);
+        d.app.op_mode = OpMode::Fractions;
+        d.app.difficulty = crate::problems::Difficulty::Hard;
+        d.start();
+        d.keys("1 1/");
+        assert_snapshot(&d.app, "fraction_playing");
+    }
+
+    #[test]
+    fn missing_operand_playing_screen() {
+        let mut d = Driver::new(1);
+        d.app.find_operand = true;
+        d.start();
+        assert_snapshot(&d.app, "missing_operand_playing");
+    }
+
+    #[test]
+    fn expression_playing_screen() {
+        let mut d = Driver::new(1);
+        d.app.op_mode = OpMode::Expressions;
+        d.start();
+        assert_snapshot(&d.app, "expression_playing");
+
+        // A line of large text holds 18 characters
+        let rng = crate::rng::SeededRng::new(1);
+        let long = core::iter::repeat_with(|| crate::problems::generate_expression(&rng, Difficulty::Hard))
+            .find(|p| p.question().len() > 18)
+            .unwrap();
+        d.app.current_problem = Some(long);
+        assert_snapshot(&d.app, "expression_wrapped");
+    }
+
+    #[test]
+    fn confirm_abandon_screen() {
+        let mut d = Driver::new(1);
+        d.start();
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        d.press(KEY_MENU);
+        d.press(KEY_MENU);
+        assert_snapshot(&d.app, "confirm_abandon");
+    }
+
+    #[test]
+    fn resume_prompt_screen() {
+        let mut d = Driver::new(1);
+        d.start();
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        d.answer_wrongly();
+        d.press(KEY_ENTER);
+        d.press(KEY_MENU);
+        d.app.save_state();
+        d.app.state = AppState::Menu;
+        d.app.load_stored();
+        assert_snapshot(&d.app, "resume_prompt");
+        d.press(KEY_MENU);
+        assert_snapshot(&d.app, "confirm_discard");
+    }
+
+    #[test]
+    fn sprint_playing_screen() {
+        let mut d = Driver::new(1);
+        d.app.session_kind = SessionKind::Sprint(60);
+        d.start();
+        d.advance(2500);
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        d.advance(40_000);
+        assert_snapshot(&d.app, "sprint_playing");
+    }
+
+    #[test]
+    fn survival_screens() {
+        let mut d = Driver::new(1);
+        d.app.session_kind = SessionKind::Survival;
+        d.start();
+        d.answer_wrongly();
+        d.press(KEY_ENTER);
+        assert_snapshot(&d.app, "survival_playing");
+        d.advance(10_000);
+        assert_snapshot(&d.app, "survival_timed_out");
+    }
+
+    #[test]
+    fn retry_after_a_timed_out_last_problem() {
+        let mut d = Driver::new(1);
+        d.app.problem_limit_secs = [5, 0, 0];
+        d.start();
+        for _ in 0..9 {
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        d.advance(5000);
+        assert!(d.app.feedback_timed_out);
+        d.press(KEY_ENTER);
+        d.press('r');
+        // A wrong retry answer is just wrong, whatever ended the session
+        d.answer_wrongly();
+        assert!(!d.app.feedback_timed_out);
+        assert_snapshot(&d.app, "retry_wrong");
+    }
+
+    #[test]
+    fn zen_screens() {
+        let mut d = Driver::new(1);
+        d.app.session_kind = SessionKind::Zen;
+        d.start();
+        assert_snapshot(&d.app, "zen_playing");
+        d.answer_wrongly();
+        assert_snapshot(&d.app, "zen_try_again");
+        d.press(KEY_ENTER);
+        d.answer_correctly();
+        d.press(KEY_ENTER);
+        // Menu pauses, and a second Menu finishes the session
+        d.press(KEY_MENU);
+        d.press(KEY_MENU);
+        assert_eq!(d.app.state, AppState::Results);
+        assert_snapshot(&d.app, "zen_results");
+    }
+
+    #[test]
+    fn feedback_screens() {
+        let mut d = Driver::new(1);
+        d.start();
+        d.answer_correctly();
+        assert_snapshot(&d.app, "feedback_correct");
+        d.press(KEY_ENTER);
+        d.answer_wrongly();
+        assert_snapshot(&d.app, "feedback_wrong");
+    }
+
+    #[test]
+    fn results_screen() {
+        let mut d = Driver::new(1);
+        d.start();
+        for i in 0..10 {
+            d.advance(1000 + i * 250);
+            if i % 4 == 1 {
+                d.answer_wrongly();
+            } else {
+                d.answer_correctly();
             }
-            None => {
-                draw_text(gam, canvas, 16, y, "  No scores yet", GlyphStyle::Small);
-                y += 18;
+            d.press(KEY_ENTER);
+        }
+        assert_snapshot(&d.app, "results");
+        d.press('r');
+        assert_snapshot(&d.app, "retry_playing");
+    }
+
+    #[test]
+    fn heatmap_screen() {
+        let mut d = Driver::new(1);
+        d.press(KEY_RIGHT);
+        d.press(KEY_RIGHT);
+        assert_eq!(d.app.op_mode, OpMode::Single(crate::problems::Operation::Multiply));
+        d.start();
+        for i in 0..10 {
+            d.advance(if i % 2 == 0 { 2000 } else { 5000 });
+            if i % 3 == 0 {
+                d.answer_wrongly();
+            } else {
+                d.answer_correctly();
             }
+            d.press(KEY_ENTER);
+        }
+        d.press(KEY_ENTER);
+        d.press(KEY_DOWN);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::Heatmap);
+        let (row, col, _) = d.app.session_problems[0].problem.arithmetic().unwrap();
+        while d.app.heatmap_row != row {
+            d.press(KEY_DOWN);
         }
-        y += 8;
+        while d.app.heatmap_col != col {
+            d.press(KEY_RIGHT);
+        }
+        assert_snapshot(&d.app, "heatmap");
     }
 
-    draw_footer(gam, canvas, "Enter=Back  Menu=Back");
-    gam.redraw().ok();
+    #[test]
+    fn best_scores_screen() {
+        let mut d = Driver::new(1);
+        d.start();
+        for _ in 0..10 {
+            d.advance(3000);
+            d.answer_correctly();
+            d.press(KEY_ENTER);
+        }
+        d.press(KEY_ENTER);
+        d.press(KEY_DOWN);
+        d.press(KEY_DOWN);
+        d.press(KEY_ENTER);
+        assert_eq!(d.app.state, AppState::BestScores);
+        assert_snapshot(&d.app, "best_scores");
+    }
 }